@@ -0,0 +1,75 @@
+//! Benchmarks of the RBN hot paths over n = 12...1000 Nodes and k = 1...5 inputs. Every network is
+//! generated from a fixed seed so runs measure the same networks. See the README for comparing
+//! against the checked-in baselines
+#[macro_use]
+extern crate criterion;
+extern crate spiky;
+
+use criterion::{BenchmarkId, Criterion};
+use spiky::rbn::RBN;
+use spiky::util::bonding::IsSubSymbolic;
+use spiky::util::cycle_calc::IsSynchronous;
+use spiky::util::recorder::NullRecorder;
+use spiky::util::rng::seeded;
+
+const SIZES: [u16; 5] = [12, 50, 100, 500, 1000];
+const INPUTS: [u8; 5] = [1, 2, 3, 4, 5];
+const SEED: u64 = 1;
+
+/// The network benchmarked at <n> and <k>
+fn network(n: u16, k: u8, bias: f64) -> RBN {
+    RBN::new_with_rng(k, n, bias, &mut seeded(SEED))
+}
+
+/// One synchronous update, every Node's next state then the switch to it
+fn step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step_sync");
+    for &k in &INPUTS {
+        for &n in &SIZES {
+            let rbn = network(n, k, 0.5);
+            group.bench_with_input(BenchmarkId::new(format!("k{}", k), n), &rbn, |b, rbn| {
+                b.iter(|| {
+                    let state = rbn.step();
+                    rbn.sync();
+                    state
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Cycle, transient and liveliness from temperature 0. Networks at bias 0.5 and k of 3 or more
+/// are chaotic, with cycles too long to find at these sizes, so every k uses a bias of 0.9,
+/// which keeps 2k·p(1-p) below 1 and the dynamics ordered
+fn calculate_particle(c: &mut Criterion) {
+    let mut group = c.benchmark_group("calculate_particle");
+    group.sample_size(20);
+    for &k in &INPUTS {
+        for &n in &SIZES {
+            let mut rbn = network(n, k, 0.9);
+            group.bench_function(BenchmarkId::new(format!("k{}", k), n), |b| {
+                b.iter(|| rbn.calculate_particle(0, &mut NullRecorder))
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Most influential first with groups as large as the network, as the default SitePolicy does
+fn interaction_groups(c: &mut Criterion) {
+    let mut group = c.benchmark_group("interaction_groups_inf");
+    group.sample_size(20);
+    for &k in &INPUTS {
+        for &n in &SIZES {
+            let rbn = network(n, k, 0.5);
+            group.bench_with_input(BenchmarkId::new(format!("k{}", k), n), &rbn, |b, rbn| {
+                b.iter(|| rbn.generate_interaction_groups_inf(n, false))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, step, calculate_particle, interaction_groups);
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+//! Loading of configuration files, TOML or JSON depending on the file extension
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    /// The file could not be parsed into the expected configuration
+    Parse(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "{}", err),
+            ConfigError::Parse(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+/// Parses <src> as TOML
+pub fn from_toml<T: DeserializeOwned>(src: &str) -> Result<T, ConfigError> {
+    toml::from_str(src).map_err(|err| ConfigError::Parse(err.to_string()))
+}
+
+/// Parses <src> as JSON
+pub fn from_json<T: DeserializeOwned>(src: &str) -> Result<T, ConfigError> {
+    serde_json::from_str(src).map_err(|err| ConfigError::Parse(err.to_string()))
+}
+
+/// Reads a .toml or .json configuration file
+pub fn load<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, ConfigError> {
+    let path = path.as_ref();
+    let src = fs::read_to_string(path)?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => from_toml(&src),
+        Some("json") => from_json(&src),
+        _ => Err(ConfigError::Parse(format!(
+            "unknown configuration format {}, expected .toml or .json",
+            path.display()
+        ))),
+    }
+}
+
+/// Checks the parameters of random atom generation, <n> Nodes with <k> inputs each and truth
+/// table entries true with probability <bias>
+pub fn check_atom_params(n: u16, k: u8, bias: f64) -> Result<(), ConfigError> {
+    if n == 0 {
+        return Err(ConfigError::Parse(
+            "an atom needs at least one Node".to_string(),
+        ));
+    }
+    if k > 7 {
+        return Err(ConfigError::Parse(format!(
+            "k = {}, at most 7 inputs are supported",
+            k
+        )));
+    }
+    if !(0.0..=1.0).contains(&bias) {
+        return Err(ConfigError::Parse(format!(
+            "bias {} is not a probability",
+            bias
+        )));
+    }
+    Ok(())
+}
+
+/// Writes <val> to <path> as JSON. The file is written next to <path> and renamed over it so an
+/// interrupted write never leaves a truncated file behind
+pub fn save_json<T: Serialize, P: AsRef<Path>>(val: &T, path: P) -> Result<(), ConfigError> {
+    let path = path.as_ref();
+    let src = serde_json::to_string(val).map_err(|err| ConfigError::Parse(err.to_string()))?;
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, src)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
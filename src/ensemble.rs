@@ -0,0 +1,404 @@
+//! Statistics over ensembles of random RBNs, the classic attractor count and cycle length against
+//! n and k plots. Every network is generated from its own seed and the networks are analysed
+//! across threads, the result does not depend on how many threads are used
+use crate::config::{check_atom_params, ConfigError};
+use crate::experiment::Range;
+use crate::rbn::RBN;
+use crate::trajectory::Trajectory;
+use crate::util::rng::seeded;
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Bins of the liveliness spectrum, normalised cycle liveliness -1..=1 in steps of 0.1
+pub const SPECTRUM_BINS: usize = 21;
+
+fn default_bias() -> f64 {
+    0.5
+}
+
+fn default_samples() -> usize {
+    100
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnsembleConfig {
+    /// Nodes per network, one ensemble is run for every n and k
+    pub n: Range<u16>,
+    /// Inputs per Node
+    pub k: Range<u8>,
+    /// Probability of each truth table entry being true
+    #[serde(default = "default_bias")]
+    pub bias: f64,
+    /// Networks in each ensemble
+    pub networks: usize,
+    pub seed: u64,
+    /// Random initial states each network is run from to find its attractors
+    #[serde(default = "default_samples")]
+    pub samples: usize,
+    /// Worker threads, 0 uses every available core
+    #[serde(default)]
+    pub threads: usize,
+}
+
+/// An attractor found from one or more initial states
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attractor {
+    pub cycle: usize,
+    /// Cycle liveliness of every Node, +1 for every cycle step on and -1 for every step off
+    pub liveliness: Vec<i32>,
+    /// Number of sampled initial states that ended on this attractor
+    pub hits: usize,
+}
+
+/// What was found in one network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkStats {
+    pub seed: u64,
+    /// Distinct attractors in the order they were first reached
+    pub attractors: Vec<Attractor>,
+    /// Transient length from every sampled initial state
+    pub transients: Vec<usize>,
+}
+
+/// Mean, standard deviation and range of a sample
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub mean: f64,
+    pub std: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    pub fn of<I: IntoIterator<Item = f64>>(vals: I) -> Summary {
+        let vals: Vec<f64> = vals.into_iter().collect();
+        if vals.is_empty() {
+            return Summary {
+                mean: 0.0,
+                std: 0.0,
+                min: 0.0,
+                max: 0.0,
+            };
+        }
+        let mean = vals.iter().sum::<f64>() / vals.len() as f64;
+        let var = vals.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / vals.len() as f64;
+        Summary {
+            mean,
+            std: var.sqrt(),
+            min: vals.iter().cloned().fold(f64::INFINITY, f64::min),
+            max: vals.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+/// Aggregated statistics of one ensemble
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnsembleStats {
+    pub n: u16,
+    pub k: u8,
+    pub networks: usize,
+    /// Distinct attractors found per network
+    pub attractors: Summary,
+    /// Cycle length of every distinct attractor
+    pub cycle: Summary,
+    /// Number of distinct attractors with each cycle length
+    pub cycle_histogram: BTreeMap<usize, usize>,
+    /// Transient length from every sampled initial state
+    pub transient: Summary,
+    /// Liveliness of every Node on every attractor divided by the cycle length, counted in
+    /// SPECTRUM_BINS bins from -1 to 1
+    pub liveliness_spectrum: Vec<usize>,
+}
+
+/// Runs <rbn> from <samples> random initial states and records the attractor each one ends on
+pub fn analyse_network<R: Rng>(rbn: &RBN, samples: usize, rng: &mut R) -> NetworkStats {
+    let n = rbn.get_node_count();
+    let mut attractors: Vec<Attractor> = Vec::new();
+    // smallest cycle state of each attractor, identifies it whatever state it is entered from
+    let mut known = HashMap::<Vec<bool>, usize>::new();
+    let mut transients = Vec::with_capacity(samples);
+    for _s in 0..samples {
+        let init: Vec<bool> = (0..n).map(|_| rng.gen()).collect();
+        let traj = Trajectory::from_state(rbn, &init);
+        transients.push(traj.transient);
+        let cycle = traj.cycle_states();
+        let key = cycle.iter().min().unwrap().clone();
+        match known.get(&key) {
+            Some(idx) => attractors[*idx].hits += 1,
+            None => {
+                known.insert(key, attractors.len());
+                attractors.push(Attractor {
+                    cycle: traj.cycle,
+                    liveliness: traj.cycle_liveliness(),
+                    hits: 1,
+                });
+            }
+        }
+    }
+    NetworkStats {
+        seed: 0,
+        attractors,
+        transients,
+    }
+}
+
+/// Every network finished so far, enough to carry on a run where it stopped
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnsembleCheckpoint {
+    pub config: EnsembleConfig,
+    /// Results of each n and k in the order they are run, None for networks not yet analysed
+    pub results: Vec<Vec<Option<NetworkStats>>>,
+}
+
+/// Generates and analyses <config.networks> networks of <n> Nodes with <k> inputs. Network <i> is
+/// generated from seed <config.seed> + <i>
+pub fn run_ensemble(config: &EnsembleConfig, n: u16, k: u8) -> Vec<NetworkStats> {
+    resume_ensemble(config, n, k, vec![None; config.networks], 0, |_| Ok(())).unwrap()
+}
+
+struct Progress<F> {
+    results: Vec<Option<NetworkStats>>,
+    finished: usize,
+    on_progress: F,
+    error: Option<ConfigError>,
+}
+
+/// Like run_ensemble but only the networks that are None in <results> are analysed. Each time
+/// another <every> networks have finished <on_progress> is called with the results so far
+pub fn resume_ensemble<F>(
+    config: &EnsembleConfig,
+    n: u16,
+    k: u8,
+    results: Vec<Option<NetworkStats>>,
+    every: usize,
+    on_progress: F,
+) -> Result<Vec<NetworkStats>, ConfigError>
+where
+    F: FnMut(&[Option<NetworkStats>]) -> Result<(), ConfigError> + Send,
+{
+    let threads = if config.threads == 0 {
+        thread::available_parallelism().map_or(1, |t| t.get())
+    } else {
+        config.threads
+    };
+    let pending: Vec<usize> = (0..results.len())
+        .filter(|idx| results[*idx].is_none())
+        .collect();
+    let next = AtomicUsize::new(0);
+    let progress = Mutex::new(Progress {
+        results,
+        finished: 0,
+        on_progress,
+        error: None,
+    });
+    thread::scope(|scope| {
+        for _t in 0..threads.min(pending.len()) {
+            scope.spawn(|| {
+                while let Some(idx) = pending.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let idx = *idx;
+                    let seed = config.seed.wrapping_add(idx as u64);
+                    let mut rng = seeded(seed);
+                    let rbn = RBN::new_with_rng(k, n, config.bias, &mut rng);
+                    let mut stats = analyse_network(&rbn, config.samples, &mut rng);
+                    stats.seed = seed;
+                    let mut guard = progress.lock().unwrap();
+                    let prog = &mut *guard;
+                    prog.results[idx] = Some(stats);
+                    prog.finished += 1;
+                    if every > 0 && prog.finished.is_multiple_of(every) && prog.error.is_none() {
+                        if let Err(err) = (prog.on_progress)(&prog.results) {
+                            prog.error = Some(err);
+                        }
+                    }
+                    if prog.error.is_some() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    let prog = progress.into_inner().unwrap();
+    if let Some(err) = prog.error {
+        return Err(err);
+    }
+    Ok(prog
+        .results
+        .into_iter()
+        .map(|stats| stats.unwrap())
+        .collect())
+}
+
+/// Aggregates the networks of one ensemble
+pub fn summarise(n: u16, k: u8, networks: &[NetworkStats]) -> EnsembleStats {
+    let attractors = networks.iter().flat_map(|net| net.attractors.iter());
+    let mut cycle_histogram = BTreeMap::new();
+    let mut liveliness_spectrum = vec![0; SPECTRUM_BINS];
+    for att in attractors.clone() {
+        *cycle_histogram.entry(att.cycle).or_insert(0) += 1;
+        for live in &att.liveliness {
+            let norm = *live as f64 / att.cycle as f64;
+            let bin = ((norm + 1.0) * (SPECTRUM_BINS - 1) as f64 / 2.0).round() as usize;
+            liveliness_spectrum[bin] += 1;
+        }
+    }
+    EnsembleStats {
+        n,
+        k,
+        networks: networks.len(),
+        attractors: Summary::of(networks.iter().map(|net| net.attractors.len() as f64)),
+        cycle: Summary::of(attractors.map(|att| att.cycle as f64)),
+        cycle_histogram,
+        transient: Summary::of(
+            networks
+                .iter()
+                .flat_map(|net| net.transients.iter().map(|t| *t as f64)),
+        ),
+        liveliness_spectrum,
+    }
+}
+
+impl EnsembleConfig {
+    /// Runs and summarises an ensemble for every n and k
+    pub fn run(&self) -> Result<Vec<EnsembleStats>, ConfigError> {
+        self.run_checkpointed(None, 0, |_| Ok(()))
+    }
+
+    /// Every n and k in the order they are run
+    fn combinations(&self) -> Result<Vec<(u16, u8)>, ConfigError> {
+        let mut all = Vec::new();
+        for n in self.n.values()? {
+            for k in self.k.values()? {
+                check_atom_params(n, k, self.bias)?;
+                all.push((n, k));
+            }
+        }
+        Ok(all)
+    }
+
+    /// Runs and summarises an ensemble for every n and k, skipping the networks already in
+    /// <resume>. <save> is called with a checkpoint each time another <every> networks have
+    /// finished and once more at the end
+    pub fn run_checkpointed<F>(
+        &self,
+        resume: Option<EnsembleCheckpoint>,
+        every: usize,
+        mut save: F,
+    ) -> Result<Vec<EnsembleStats>, ConfigError>
+    where
+        F: FnMut(&EnsembleCheckpoint) -> Result<(), ConfigError> + Send,
+    {
+        let combos = self.combinations()?;
+        let mut prior = match resume {
+            Some(checkpoint) => {
+                if checkpoint.config != *self
+                    || checkpoint.results.len() != combos.len()
+                    || checkpoint.results.iter().any(|r| r.len() != self.networks)
+                {
+                    return Err(ConfigError::Parse(
+                        "checkpoint does not belong to this ensemble".to_string(),
+                    ));
+                }
+                checkpoint.results
+            }
+            None => vec![vec![None; self.networks]; combos.len()],
+        };
+        let mut done: Vec<Vec<Option<NetworkStats>>> = Vec::with_capacity(combos.len());
+        let mut all = Vec::with_capacity(combos.len());
+        for (idx, (n, k)) in combos.iter().enumerate() {
+            let results = std::mem::take(&mut prior[idx]);
+            let networks = resume_ensemble(self, *n, *k, results, every, |current| {
+                let mut results = done.clone();
+                results.push(current.to_vec());
+                results.extend(prior[idx + 1..].iter().cloned());
+                save(&EnsembleCheckpoint {
+                    config: self.clone(),
+                    results,
+                })
+            })?;
+            all.push(summarise(*n, *k, &networks));
+            done.push(networks.into_iter().map(Some).collect());
+        }
+        save(&EnsembleCheckpoint {
+            config: self.clone(),
+            results: done,
+        })?;
+        Ok(all)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn threads_do_not_change_results() {
+        assert_send::<RBN>();
+        let mut config = EnsembleConfig {
+            n: Range::One(10),
+            k: Range::One(2),
+            bias: 0.5,
+            networks: 12,
+            seed: 3,
+            samples: 20,
+            threads: 1,
+        };
+        let single = run_ensemble(&config, 10, 2);
+        config.threads = 4;
+        let multi = run_ensemble(&config, 10, 2);
+        assert_eq!(single, multi);
+        let stats = summarise(10, 2, &multi);
+        assert_eq!(
+            stats.cycle_histogram.values().sum::<usize>() as f64,
+            stats.attractors.mean * 12.0
+        );
+        assert_eq!(
+            stats.liveliness_spectrum.iter().sum::<usize>(),
+            stats.cycle_histogram.values().sum::<usize>() * 10
+        );
+    }
+
+    #[test]
+    fn attractors_of_a_known_network() {
+        // n0 = !n1, n1 = n0 is a single 4 cycle, n2 = n2 splits it into two attractors
+        let tbls = vec![vec![true, false], vec![false, true], vec![false, true]];
+        let rbn = RBN::new_from_def_k(tbls, vec![vec![1], vec![0], vec![2]]);
+        let stats = analyse_network(&rbn, 50, &mut seeded(1));
+        assert_eq!(stats.attractors.len(), 2);
+        assert!(stats.attractors.iter().all(|a| a.cycle == 4));
+        assert!(stats.transients.iter().all(|t| *t == 0));
+        assert_eq!(stats.attractors.iter().map(|a| a.hits).sum::<usize>(), 50);
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let config = EnsembleConfig {
+            n: Range::List(vec![8, 10]),
+            k: Range::One(2),
+            bias: 0.5,
+            networks: 6,
+            seed: 5,
+            samples: 10,
+            threads: 2,
+        };
+        let whole = config.run().unwrap();
+        let mut saved = Vec::new();
+        config
+            .run_checkpointed(None, 4, |cp| {
+                saved.push(cp.clone());
+                Ok(())
+            })
+            .unwrap();
+        // the first checkpoint is part way through the first ensemble
+        let first = saved[0].clone();
+        assert_eq!(first.results[0].iter().filter(|r| r.is_some()).count(), 4);
+        assert!(first.results[1].iter().all(|r| r.is_none()));
+        let resumed = config.run_checkpointed(Some(first), 0, |_| Ok(())).unwrap();
+        assert_eq!(resumed, whole);
+    }
+}
@@ -0,0 +1,372 @@
+//! Genetic algorithm search for atoms with target properties. Genomes are atom definitions, they
+//! are mutated by flipping truth table bits and rewiring inputs, recombined by taking each Node
+//! from one parent or the other and selected by tournament with the best kept unchanged
+use crate::config::{check_atom_params, ConfigError};
+use crate::formats::json::AtomDef;
+use crate::rbn::RBN;
+use crate::temp::Temperature;
+use crate::util::bonding::{IsBondable, IsSubSymbolic};
+use crate::util::recorder::NullRecorder;
+use crate::util::rng::{seeded, SpikyRng};
+use rand::Rng;
+use std::cmp::Ordering;
+
+/// Scores an atom, higher is fitter
+pub trait Fitness {
+    fn fitness(&self, atom: &AtomDef) -> f64;
+}
+
+impl<F: Fn(&AtomDef) -> f64> Fitness for F {
+    fn fitness(&self, atom: &AtomDef) -> f64 {
+        self(atom)
+    }
+}
+
+/// Builds and calculates <atom> from <temperature>, returning the RBN with its cycle and transient
+fn calculate(atom: &AtomDef, temperature: Temperature) -> (RBN, u64, u64) {
+    let mut rbn = atom.to_rbn().expect("genomes are always valid atoms");
+    let (cycle, transient) = rbn
+        .calculate_particle(temperature, &mut NullRecorder)
+        .lengths();
+    (rbn, cycle, transient)
+}
+
+/// Built in fitness functions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Objective {
+    /// Cycle length from <temperature> as close to <target> as possible
+    CycleLength {
+        target: u64,
+        #[serde(default)]
+        temperature: Temperature,
+    },
+    /// Transient length from <temperature> as close to <target> as possible
+    TransientLength {
+        target: u64,
+        #[serde(default)]
+        temperature: Temperature,
+    },
+    /// Spikes of the bonding sites as close to <target> as possible, site by site. Missing or
+    /// extra sites count their whole spike against the atom
+    SpikeProfile {
+        target: Vec<i32>,
+        /// Largest interaction group, the atom's Node count if not given
+        #[serde(default)]
+        group_size: Option<u16>,
+        #[serde(default)]
+        temperature: Temperature,
+    },
+    /// As many different cycle lengths as possible across <temperatures>, atoms whose behaviour
+    /// depends on where they start
+    TemperatureSensitivity { temperatures: Vec<Temperature> },
+}
+
+impl Fitness for Objective {
+    fn fitness(&self, atom: &AtomDef) -> f64 {
+        match self {
+            Objective::CycleLength {
+                target,
+                temperature,
+            } => {
+                let (_rbn, cycle, _transient) = calculate(atom, *temperature);
+                -((cycle as f64) - (*target as f64)).abs()
+            }
+            Objective::TransientLength {
+                target,
+                temperature,
+            } => {
+                let (_rbn, _cycle, transient) = calculate(atom, *temperature);
+                -((transient as f64) - (*target as f64)).abs()
+            }
+            Objective::SpikeProfile {
+                target,
+                group_size,
+                temperature,
+            } => {
+                let (rbn, _cycle, _transient) = calculate(atom, *temperature);
+                let size = group_size.unwrap_or(rbn.get_node_count() as u16);
+                let spikes: Vec<i32> = rbn
+                    .generate_interaction_groups_inf(size, false)
+                    .iter()
+                    .map(|site| rbn.get_bonding_prop(site).unwrap_or(0))
+                    .collect();
+                let mut dist = 0;
+                for idx in 0..spikes.len().max(target.len()) {
+                    let have = spikes.get(idx).cloned().unwrap_or(0);
+                    let want = target.get(idx).cloned().unwrap_or(0);
+                    dist += (have - want).abs();
+                }
+                -(dist as f64)
+            }
+            Objective::TemperatureSensitivity { temperatures } => {
+                let mut cycles: Vec<u64> =
+                    temperatures.iter().map(|t| calculate(atom, *t).1).collect();
+                cycles.sort();
+                cycles.dedup();
+                cycles.len() as f64
+            }
+        }
+    }
+}
+
+/// A random atom of <n> Nodes with <k> inputs each
+pub fn random_atom<R: Rng>(n: u16, k: u8, bias: f64, rng: &mut R) -> AtomDef {
+    AtomDef::from_rbn(&RBN::new_with_rng(k, n, bias, rng))
+}
+
+/// Flips every truth table bit with probability <bit_flip> and moves every input to a random
+/// Node with probability <rewire>
+pub fn mutate<R: Rng>(atom: &mut AtomDef, bit_flip: f64, rewire: f64, rng: &mut R) {
+    let n = atom.tables.len();
+    for tbl in atom.tables.iter_mut() {
+        for bit in tbl.iter_mut() {
+            if rng.gen_bool(bit_flip) {
+                *bit = !*bit;
+            }
+        }
+    }
+    for inputs in atom.inputs.iter_mut() {
+        for src in inputs.iter_mut() {
+            if rng.gen_bool(rewire) {
+                *src = rng.gen_range(0, n);
+            }
+        }
+    }
+}
+
+/// Uniform crossover of Nodes, Node i of the child is Node i of <a> or of <b> with equal chance,
+/// truth table and inputs together. The parents must have the same number of Nodes
+pub fn crossover<R: Rng>(a: &AtomDef, b: &AtomDef, rng: &mut R) -> AtomDef {
+    let mut child = a.clone();
+    for idx in 0..child.tables.len() {
+        if rng.gen_bool(0.5) {
+            child.tables[idx] = b.tables[idx].clone();
+            child.inputs[idx] = b.inputs[idx].clone();
+        }
+    }
+    child
+}
+
+fn default_bias() -> f64 {
+    0.5
+}
+
+fn default_tournament() -> usize {
+    3
+}
+
+fn default_elitism() -> usize {
+    1
+}
+
+fn default_crossover() -> f64 {
+    0.7
+}
+
+fn default_rate() -> f64 {
+    0.01
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvolveConfig {
+    /// Nodes per atom
+    pub n: u16,
+    /// Inputs per Node
+    pub k: u8,
+    /// Probability of each truth table entry of the initial atoms being true
+    #[serde(default = "default_bias")]
+    pub bias: f64,
+    pub population: usize,
+    pub generations: u64,
+    pub seed: u64,
+    /// Atoms in each selection tournament
+    #[serde(default = "default_tournament")]
+    pub tournament: usize,
+    /// Fittest atoms carried into the next generation unchanged
+    #[serde(default = "default_elitism")]
+    pub elitism: usize,
+    /// Probability a child is bred from two parents rather than copied from one
+    #[serde(default = "default_crossover")]
+    pub crossover: f64,
+    /// Probability of each truth table bit flipping in a child
+    #[serde(default = "default_rate")]
+    pub bit_flip: f64,
+    /// Probability of each input being rewired in a child
+    #[serde(default = "default_rate")]
+    pub rewire: f64,
+    pub fitness: Objective,
+}
+
+impl EvolveConfig {
+    /// Checks the configuration describes a search that can be run
+    pub fn check(&self) -> Result<(), ConfigError> {
+        check_atom_params(self.n, self.k, self.bias)?;
+        if self.population == 0 || self.tournament == 0 {
+            return Err(ConfigError::Parse(
+                "population and tournament must be at least 1".to_string(),
+            ));
+        }
+        if self.elitism > self.population {
+            return Err(ConfigError::Parse(format!(
+                "elitism {} is larger than the population {}",
+                self.elitism, self.population
+            )));
+        }
+        for (name, p) in &[
+            ("crossover", self.crossover),
+            ("bit_flip", self.bit_flip),
+            ("rewire", self.rewire),
+        ] {
+            if !(0.0..=1.0).contains(p) {
+                return Err(ConfigError::Parse(format!(
+                    "{} {} is not a probability",
+                    name, p
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fitness of one generation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GenerationStats {
+    pub generation: u64,
+    pub best: f64,
+    pub mean: f64,
+}
+
+pub struct Evolution<F: Fitness> {
+    pub config: EvolveConfig,
+    pub fitness: F,
+    /// Current population and fitness, fittest first
+    pub population: Vec<(AtomDef, f64)>,
+    pub generation: u64,
+    rng: SpikyRng,
+}
+
+fn by_fitness(a: &(AtomDef, f64), b: &(AtomDef, f64)) -> Ordering {
+    b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal)
+}
+
+impl<F: Fitness> Evolution<F> {
+    /// Random initial population drawn from <config.seed>
+    pub fn new(config: EvolveConfig, fitness: F) -> Evolution<F> {
+        let mut rng = seeded(config.seed);
+        let mut population: Vec<(AtomDef, f64)> = (0..config.population)
+            .map(|_| {
+                let atom = random_atom(config.n, config.k, config.bias, &mut rng);
+                let score = fitness.fitness(&atom);
+                (atom, score)
+            })
+            .collect();
+        population.sort_by(by_fitness);
+        Evolution {
+            config,
+            fitness,
+            population,
+            generation: 0,
+            rng,
+        }
+    }
+
+    /// The fittest atom in the population
+    pub fn best(&self) -> &(AtomDef, f64) {
+        &self.population[0]
+    }
+
+    pub fn stats(&self) -> GenerationStats {
+        GenerationStats {
+            generation: self.generation,
+            best: self.population[0].1,
+            mean: self.population.iter().map(|p| p.1).sum::<f64>() / self.population.len() as f64,
+        }
+    }
+
+    /// Fittest of <config.tournament> atoms drawn at random
+    fn tournament(&mut self) -> usize {
+        let mut winner = self.rng.gen_range(0, self.population.len());
+        for _t in 1..self.config.tournament {
+            // the population is sorted so the lower index is at least as fit
+            winner = winner.min(self.rng.gen_range(0, self.population.len()));
+        }
+        winner
+    }
+
+    /// Breeds the next generation
+    pub fn step(&mut self) {
+        let mut next: Vec<(AtomDef, f64)> = self.population[..self.config.elitism].to_vec();
+        while next.len() < self.config.population {
+            let a = self.tournament();
+            let mut child = if self.rng.gen_bool(self.config.crossover) {
+                let b = self.tournament();
+                crossover(&self.population[a].0, &self.population[b].0, &mut self.rng)
+            } else {
+                self.population[a].0.clone()
+            };
+            mutate(
+                &mut child,
+                self.config.bit_flip,
+                self.config.rewire,
+                &mut self.rng,
+            );
+            let score = self.fitness.fitness(&child);
+            next.push((child, score));
+        }
+        next.sort_by(by_fitness);
+        self.population = next;
+        self.generation += 1;
+    }
+
+    /// Runs the remaining generations, calling <on_generation> after each, including the initial
+    /// population
+    pub fn run<G: FnMut(&GenerationStats)>(&mut self, mut on_generation: G) {
+        on_generation(&self.stats());
+        while self.generation < self.config.generations {
+            self.step();
+            on_generation(&self.stats());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    fn config() -> EvolveConfig {
+        config::from_toml(
+            "n = 8\nk = 2\npopulation = 20\ngenerations = 15\nseed = 4\n\
+             [fitness]\nkind = \"cycle_length\"\ntarget = 6\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn operators_keep_atoms_valid() {
+        let mut rng = seeded(1);
+        let a = random_atom(6, 3, 0.5, &mut rng);
+        let b = random_atom(6, 3, 0.5, &mut rng);
+        let mut child = crossover(&a, &b, &mut rng);
+        for idx in 0..6 {
+            assert!(child.tables[idx] == a.tables[idx] || child.tables[idx] == b.tables[idx]);
+        }
+        mutate(&mut child, 0.5, 0.5, &mut rng);
+        assert!(child.to_rbn().is_ok());
+    }
+
+    #[test]
+    fn evolution_is_reproducible_and_elitist() {
+        let mut first = Evolution::new(config(), config().fitness);
+        let mut history = Vec::new();
+        first.run(|s| history.push(s.clone()));
+        assert_eq!(history.len(), 16);
+        // the best is never lost
+        assert!(history.windows(2).all(|w| w[1].best >= w[0].best));
+        let mut second = Evolution::new(config(), config().fitness);
+        second.run(|_| {});
+        assert_eq!(first.best(), second.best());
+    }
+}
@@ -0,0 +1,232 @@
+//! Parameter sweeps over random atoms. An experiment spec declares ranges for every parameter, the
+//! runner expands the grid, generates and calculates an atom for every point and replicate and
+//! writes one CSV row for each. Atoms are seeded from the spec so a rerun gives the same table
+use crate::config::{check_atom_params, ConfigError};
+use crate::rbn::RBN;
+use crate::temp::Temperature;
+use crate::util::bonding::{IsBondable, IsSubSymbolic};
+use crate::util::recorder::NullRecorder;
+use crate::util::rng::seeded;
+use std::io::Write;
+
+/// Parameter types a Range can step through
+pub trait Param: Copy {
+    fn to_f64(self) -> f64;
+    fn from_f64(val: f64) -> Self;
+}
+
+macro_rules! int_param {
+    ($t:ty) => {
+        impl Param for $t {
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+            fn from_f64(val: f64) -> Self {
+                val.round() as $t
+            }
+        }
+    };
+}
+int_param!(u8);
+int_param!(u16);
+
+impl Param for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+    /// Rounded to 12 decimals so steps like 0.1 do not print as 0.30000000000000004
+    fn from_f64(val: f64) -> Self {
+        (val * 1e12).round() / 1e12
+    }
+}
+
+/// Values of one parameter, a single value, a list or <from> to <to> inclusive in steps of <step>
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Range<T> {
+    One(T),
+    List(Vec<T>),
+    Step { from: T, to: T, step: T },
+}
+
+impl<T: Param> Range<T> {
+    pub fn values(&self) -> Result<Vec<T>, ConfigError> {
+        match self {
+            Range::One(val) => Ok(vec![*val]),
+            Range::List(vals) => Ok(vals.clone()),
+            Range::Step { from, to, step } => {
+                let (from, to, step) = (from.to_f64(), to.to_f64(), step.to_f64());
+                if step <= 0.0 {
+                    return Err(ConfigError::Parse(format!("step {} is not positive", step)));
+                }
+                // a little slack so rounding does not drop the last value
+                let count = ((to - from) / step + 1e-9).floor() as i64 + 1;
+                Ok((0..count.max(0))
+                    .map(|idx| T::from_f64(from + idx as f64 * step))
+                    .collect())
+            }
+        }
+    }
+}
+
+fn default_bias() -> Range<f64> {
+    Range::One(0.5)
+}
+
+fn default_temperature() -> Range<Temperature> {
+    Range::One(0)
+}
+
+fn default_replicates() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExperimentSpec {
+    /// Nodes per atom
+    pub n: Range<u16>,
+    /// Inputs per Node
+    pub k: Range<u8>,
+    /// Probability of each truth table entry being true
+    #[serde(default = "default_bias")]
+    pub bias: Range<f64>,
+    /// Largest interaction group, each atom's Node count if not given
+    #[serde(default)]
+    pub group_size: Option<Range<u16>>,
+    /// Initial states each atom is calculated from
+    #[serde(default = "default_temperature")]
+    pub temperature: Range<Temperature>,
+    /// Atoms generated for each n, k and bias
+    #[serde(default = "default_replicates")]
+    pub replicates: usize,
+    pub seed: u64,
+}
+
+/// One row of the results table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub n: u16,
+    pub k: u8,
+    pub bias: f64,
+    pub group_size: u16,
+    pub temperature: Temperature,
+    pub replicate: usize,
+    /// Seed the atom was generated from
+    pub seed: u64,
+}
+
+pub const HEADER: &str =
+    "n,k,bias,group_size,temperature,replicate,seed,cycle,transient,sites,mean_site_size,spikes";
+
+impl ExperimentSpec {
+    /// Every point of the grid. Atoms depend only on n, k, bias and replicate, so all group sizes
+    /// and temperatures of a replicate are measured on the same atom
+    pub fn expand(&self) -> Result<Vec<Point>, ConfigError> {
+        let bias = self.bias.values()?;
+        let temperature = self.temperature.values()?;
+        let group_size = match &self.group_size {
+            Some(range) => Some(range.values()?),
+            None => None,
+        };
+        let mut points = Vec::new();
+        let mut atom_idx = 0u64;
+        for n in self.n.values()? {
+            for k in self.k.values()? {
+                for b in &bias {
+                    check_atom_params(n, k, *b)?;
+                    for replicate in 0..self.replicates {
+                        let seed = self.seed.wrapping_add(atom_idx);
+                        atom_idx += 1;
+                        let sizes = group_size.clone().unwrap_or_else(|| vec![n]);
+                        for size in sizes {
+                            for t in &temperature {
+                                points.push(Point {
+                                    n,
+                                    k,
+                                    bias: *b,
+                                    group_size: size,
+                                    temperature: *t,
+                                    replicate,
+                                    seed,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(points)
+    }
+
+    /// Runs every point of the grid and writes the results table to <out>
+    pub fn run<W: Write>(&self, mut out: W) -> Result<(), ConfigError> {
+        let points = self.expand()?;
+        writeln!(out, "{}", HEADER)?;
+        for point in points {
+            writeln!(out, "{}", run_point(&point))?;
+        }
+        Ok(())
+    }
+}
+
+/// Generates, calculates and splits the atom of <point> into bonding sites, returning the CSV row
+pub fn run_point(point: &Point) -> String {
+    let mut rbn = RBN::new_with_rng(point.k, point.n, point.bias, &mut seeded(point.seed));
+    let (cycle, transient) = rbn
+        .calculate_particle(point.temperature, &mut NullRecorder)
+        .lengths();
+    let sites = rbn.generate_interaction_groups_inf(point.group_size, false);
+    let spikes: Vec<String> = sites
+        .iter()
+        .map(|site| match rbn.get_bonding_prop(site) {
+            Some(spike) => spike.to_string(),
+            None => String::new(),
+        })
+        .collect();
+    format!(
+        "{},{},{},{},{},{},{},{},{},{},{},{}",
+        point.n,
+        point.k,
+        point.bias,
+        point.group_size,
+        point.temperature,
+        point.replicate,
+        point.seed,
+        cycle,
+        transient,
+        sites.len(),
+        point.n as f64 / sites.len() as f64,
+        spikes.join(";")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    #[test]
+    fn expand_and_rerun() {
+        let spec: ExperimentSpec = config::from_toml(
+            "n = [8, 10]\nk = 2\nbias = { from = 0.1, to = 0.3, step = 0.1 }\n\
+             temperature = [0, 5]\nreplicates = 2\nseed = 11\n",
+        )
+        .unwrap();
+        let points = spec.expand().unwrap();
+        assert_eq!(points.len(), 2 * 3 * 2 * 2);
+        assert_eq!(points[2].bias, 0.1);
+        assert_eq!(points[4].bias, 0.2);
+        assert_eq!(points[9].bias, 0.3);
+        // both temperatures of a replicate share an atom
+        assert_eq!(points[0].seed, points[1].seed);
+        assert_ne!(points[0].seed, points[2].seed);
+
+        let mut first = Vec::new();
+        spec.run(&mut first).unwrap();
+        let mut second = Vec::new();
+        spec.run(&mut second).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(String::from_utf8(first).unwrap().lines().count(), 25);
+    }
+}
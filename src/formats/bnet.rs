@@ -0,0 +1,54 @@
+//! BNET format used by PyBoolNet and the CoLoMoTo tools, one "target, expression" line per Node
+//! with an optional "targets, factors" header
+use crate::formats::{build_rbn, export_rules, parse_rules, FormatError};
+use crate::rbn::RBN;
+use std::fs;
+use std::path::Path;
+
+/// Parses a BNET network description into an RBN
+pub fn from_bnet(src: &str) -> Result<RBN, FormatError> {
+    build_rbn(parse_rules(src, false)?)
+}
+
+/// Writes <rbn> in BNET format, Node functions as minimal DNF with the targets column aligned
+pub fn to_bnet(rbn: &RBN) -> String {
+    let rules = export_rules(rbn);
+    let width = rules.iter().map(|r| r.0.len()).max().unwrap_or(0) + 1;
+    let mut form_string = String::new();
+    for (target, factors) in rules {
+        form_string.push_str(&format!(
+            "{:<width$} {}\n",
+            format!("{},", target),
+            factors,
+            width = width
+        ));
+    }
+    form_string
+}
+
+pub fn read_bnet<P: AsRef<Path>>(path: P) -> Result<RBN, FormatError> {
+    from_bnet(&fs::read_to_string(path)?)
+}
+
+pub fn write_bnet<P: AsRef<Path>>(rbn: &RBN, path: P) -> Result<(), FormatError> {
+    fs::write(path, to_bnet(rbn))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bnet_parse() {
+        let src = "# toy network\ntargets, factors\nA, B & !C\nB, A | C\nC, 1\n";
+        let rbn = from_bnet(src).unwrap();
+        assert_eq!(rbn.get_node_count(), 3);
+        assert_eq!(*rbn.get_node_inputs(0), vec![1, 2]);
+        assert_eq!(rbn.get_function_table(0), vec![false, true, false, false]);
+        assert_eq!(rbn.get_function_table(2), vec![true]);
+        assert_eq!(to_bnet(&rbn), "n0, n1 & !n2\nn1, n0 | n2\nn2, 1\n");
+        // header is optional
+        assert!(from_bnet("A, !A\n").is_ok());
+    }
+}
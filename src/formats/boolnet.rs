@@ -0,0 +1,82 @@
+//! BoolNet (R package) `.bn` format, a "targets, factors" header followed by one
+//! "target, expression" line per Node
+use crate::formats::{build_rbn, export_rules, parse_rules, FormatError};
+use crate::rbn::RBN;
+use std::fs;
+use std::path::Path;
+
+/// Parses a BoolNet network description into an RBN
+pub fn from_boolnet(src: &str) -> Result<RBN, FormatError> {
+    build_rbn(parse_rules(src, true)?)
+}
+
+/// Writes <rbn> in BoolNet format, Node functions as minimal DNF
+pub fn to_boolnet(rbn: &RBN) -> String {
+    let mut form_string = String::new();
+    form_string.push_str("targets, factors\n");
+    for (target, factors) in export_rules(rbn) {
+        form_string.push_str(&format!("{}, {}\n", target, factors));
+    }
+    form_string
+}
+
+pub fn read_boolnet<P: AsRef<Path>>(path: P) -> Result<RBN, FormatError> {
+    from_boolnet(&fs::read_to_string(path)?)
+}
+
+pub fn write_boolnet<P: AsRef<Path>>(rbn: &RBN, path: P) -> Result<(), FormatError> {
+    fs::write(path, to_boolnet(rbn))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::cycle_calc::IsSynchronous;
+    use crate::util::formatters::IsFormatable;
+
+    #[test]
+    fn boolnet_roundtrip() {
+        let tbls = vec![
+            vec![true, false, false, true],
+            vec![false, true, true, true],
+            vec![true, true, false, false],
+            vec![false, false, false, false],
+        ];
+        let strct = vec![(1, 2), (0, 3), (2, 2), (0, 1)];
+        let rbn = RBN::new_from_def(tbls, strct);
+        let text = to_boolnet(&rbn);
+        assert_eq!(
+            text,
+            "targets, factors\nn0, (!n1 & !n2) | (n1 & n2)\nn1, n0 | n3\nn2, !n2\nn3, 0\n"
+        );
+        let back = from_boolnet(&text).unwrap();
+        assert_eq!(to_boolnet(&back), text);
+        assert_eq!(back.get_node_inputs(3).len(), 0);
+        // same dynamics from the same start
+        for start in 0..16u16 {
+            rbn.set_state(&start.into());
+            back.set_state(&start.into());
+            for _step in 0..8 {
+                rbn.step();
+                rbn.sync();
+                back.step();
+                back.sync();
+                assert_eq!(rbn.fmt_state(), back.fmt_state());
+            }
+        }
+    }
+
+    #[test]
+    fn boolnet_errors() {
+        assert!(from_boolnet("a, b\n").is_err());
+        match from_boolnet("targets, factors\na, b\n") {
+            Err(FormatError::Parse { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("unknown node accepted"),
+        }
+        match from_boolnet("targets, factors\na, a\na, !a\n") {
+            Err(FormatError::Parse { line, .. }) => assert_eq!(line, 3),
+            _ => panic!("duplicate target accepted"),
+        }
+    }
+}
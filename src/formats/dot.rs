@@ -0,0 +1,154 @@
+//! Graphviz DOT export of RBN wiring, bonding sites and the bonds of composite particles
+use crate::util::formatters::IsFormatable;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Fill colours cycled through in DOT graphs, for the bonding sites of an RBN and the basins of
+/// the attractors of a state transition graph
+pub const DOT_PALETTE: [&str; 8] = [
+    "#a6cee3", "#b2df8a", "#fb9a99", "#fdbf6f", "#cab2d6", "#ffff99", "#1f78b4", "#33a02c",
+];
+
+/// Wraps the DOT body of <comp> in a complete digraph. compound is set so bonds can be drawn
+/// between bonding site clusters
+pub fn to_dot<T: IsFormatable + ?Sized>(comp: &T) -> String {
+    let mut form_string = String::new();
+    form_string.push_str(
+        "digraph spiky {\ncompound=true;\nnode [shape=box, style=filled, fillcolor=white];\n",
+    );
+    form_string.push_str(&comp.fmt_dot(""));
+    form_string.push_str("}\n");
+    form_string
+}
+
+pub fn write_dot<T: IsFormatable + ?Sized, P: AsRef<Path>>(comp: &T, path: P) -> io::Result<()> {
+    fs::write(path, to_dot(comp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::{Component, Particle};
+    use crate::rbn::RBN;
+    use crate::util::bonding::{Bond, IsBondable};
+    use crate::util::grouping::{Grouping, SitePolicy};
+
+    fn small_rbn() -> RBN {
+        let tbls = vec![
+            vec![true, false, false, true],
+            vec![false, true, true, true],
+            vec![true, true, false, false],
+        ];
+        RBN::new_from_def(tbls, vec![(1, 2), (0, 2), (2, 2)])
+    }
+
+    #[test]
+    fn dot_rbn() {
+        let mut rbn = small_rbn();
+        rbn.generate_bonding_sites();
+        let dot = to_dot(&rbn);
+        assert!(dot.starts_with("digraph spiky {\n"));
+        assert!(dot.contains("n2 [label=\"2\\n1100\\ninf 4\"];\n"));
+        assert!(dot.contains("n1 -> n0 [label=\"0\"];\n"));
+        assert!(dot.contains("n2 -> n0 [label=\"1\"];\n"));
+        // every node is drawn exactly once whatever the site layout
+        for idx in 0..3 {
+            assert_eq!(dot.matches(&format!("\nn{} [label", idx)).count(), 1);
+        }
+        assert!(dot.contains("subgraph cluster_s0 {\n"));
+    }
+
+    #[test]
+    fn dot_particle_bond() {
+        let comps: Vec<Box<dyn Component>> = vec![Box::new(small_rbn()), Box::new(small_rbn())];
+        let mut particle = Particle::new(comps);
+        particle.bonds.push(Bond {
+            comp_a: 0,
+            site_a: 0,
+            comp_b: 1,
+            site_b: 0,
+        });
+        let dot = to_dot(&particle);
+        assert!(dot.contains("subgraph cluster_c0 {\n"));
+        assert!(dot.contains("subgraph cluster_c1_s0 {\n"));
+        assert!(dot.contains("c0_n2 -> c0_n0 [label=\"1\"];\n"));
+        assert!(dot.contains("[ltail=cluster_c0_s0, lhead=cluster_c1_s0, dir=none"));
+    }
+
+    #[test]
+    fn dot_nested_particle() {
+        let pair = || {
+            let comps: Vec<Box<dyn Component>> = vec![Box::new(small_rbn()), Box::new(small_rbn())];
+            let mut particle = Particle::new(comps);
+            particle.bonds.push(Bond {
+                comp_a: 0,
+                site_a: 0,
+                comp_b: 1,
+                site_b: 0,
+            });
+            particle
+        };
+        let (left, right) = (pair(), pair());
+        // the last site of the left pair is on its second atom
+        let last = left.get_all_bonding_sites().len() - 1;
+        let (_comp, atom_site) = left.locate_site(last).unwrap();
+        let comps: Vec<Box<dyn Component>> = vec![Box::new(left), Box::new(right)];
+        let mut nested = Particle::new(comps);
+        nested.bonds.push(Bond {
+            comp_a: 0,
+            site_a: last,
+            comp_b: 1,
+            site_b: 0,
+        });
+        let dot = to_dot(&nested);
+        // bond edges end on the site clusters of the atoms, wherever they are nested
+        assert!(dot.contains(&format!(
+            "[ltail=cluster_c0_c1_s{}, lhead=cluster_c1_c0_s0, dir=none",
+            atom_site
+        )));
+        for end in dot.split(['=', ',']).filter(|w| w.starts_with("cluster_")) {
+            assert!(dot.contains(&format!("subgraph {} {{\n", end)), "{}", end);
+        }
+    }
+
+    #[test]
+    fn dot_overlapping_sites() {
+        // n0 <- n1 <- n2 <- n3, both sites run on into the next Node so n1 is in both
+        let tbls = vec![vec![false, true, true, false]; 4];
+        let mut rbn =
+            RBN::new_from_def_k(tbls, vec![vec![1, 1], vec![2, 2], vec![3, 0], vec![3, 0]]);
+        rbn.set_site_policy(SitePolicy {
+            grouping: Grouping::Partitions {
+                groups: vec![vec![0], vec![1]],
+            },
+            max_group_size: Some(2),
+            overlap: true,
+            ..SitePolicy::default()
+        });
+        rbn.generate_bonding_sites();
+        let dot = to_dot(&rbn);
+        for idx in 0..4 {
+            assert_eq!(dot.matches(&format!("\nn{} [label", idx)).count(), 1);
+        }
+        assert!(dot
+            .lines()
+            .any(|l| l.starts_with("n1 [") && l.ends_with(", xlabel=\"sites 0 1\"];")));
+        let second = dot.split("subgraph cluster_s1 {\n").nth(1).unwrap();
+        let second = second.split('}').next().unwrap();
+        assert!(second.contains("n2 [label") && !second.contains("n1 [label"));
+
+        // a bond on the second site ends on its own Node, inside its cluster
+        let comps: Vec<Box<dyn Component>> = vec![Box::new(rbn), Box::new(small_rbn())];
+        let mut particle = Particle::new(comps);
+        particle.bonds.push(Bond {
+            comp_a: 0,
+            site_a: 1,
+            comp_b: 1,
+            site_b: 0,
+        });
+        let dot = to_dot(&particle);
+        assert!(dot.contains("c0_n2 -> c1_n"));
+        assert!(dot.contains("[ltail=cluster_c0_s1, lhead=cluster_c1_s0, dir=none"));
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Boolean expression as found in the factors column of BoolNet and BNET files
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Const(bool),
+    Var(String),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+impl Expr {
+    /// Evaluates the expression, <lookup> gives the value of each variable by name
+    pub fn eval<F: Fn(&str) -> bool>(&self, lookup: &F) -> bool {
+        match self {
+            Expr::Const(val) => *val,
+            Expr::Var(name) => lookup(name),
+            Expr::Not(inner) => !inner.eval(lookup),
+            Expr::And(terms) => terms.iter().all(|t| t.eval(lookup)),
+            Expr::Or(terms) => terms.iter().any(|t| t.eval(lookup)),
+        }
+    }
+
+    /// Names of all variables used in the expression, sorted and without duplicates
+    pub fn variables(&self) -> BTreeSet<String> {
+        let mut vars = BTreeSet::new();
+        self.collect_vars(&mut vars);
+        vars
+    }
+
+    fn collect_vars(&self, vars: &mut BTreeSet<String>) {
+        match self {
+            Expr::Const(_) => {}
+            Expr::Var(name) => {
+                vars.insert(name.clone());
+            }
+            Expr::Not(inner) => inner.collect_vars(vars),
+            Expr::And(terms) | Expr::Or(terms) => {
+                for t in terms {
+                    t.collect_vars(vars);
+                }
+            }
+        }
+    }
+
+    /// Builds the truth table of the expression over <inputs>, the first input being the least
+    /// significant bit of the table index (same ordering as Node::calc_next_state)
+    pub fn truth_table(&self, inputs: &[String]) -> Vec<bool> {
+        let mut tbl = Vec::with_capacity(1 << inputs.len());
+        for row in 0..(1usize << inputs.len()) {
+            tbl.push(self.eval(&|name: &str| {
+                let pos = inputs
+                    .iter()
+                    .position(|i| i == name)
+                    .expect("Variable not in input list");
+                (row >> pos) & 1 == 1
+            }));
+        }
+        tbl
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(val) => write!(f, "{}", *val as u8),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Not(inner) => match **inner {
+                Expr::Const(_) | Expr::Var(_) | Expr::Not(_) => write!(f, "!{}", inner),
+                _ => write!(f, "!({})", inner),
+            },
+            Expr::And(terms) => {
+                let parts: Vec<String> = terms
+                    .iter()
+                    .map(|t| match t {
+                        Expr::Or(_) => format!("({})", t),
+                        _ => format!("{}", t),
+                    })
+                    .collect();
+                write!(f, "{}", parts.join(" & "))
+            }
+            Expr::Or(terms) => {
+                let parts: Vec<String> = terms
+                    .iter()
+                    .map(|t| match t {
+                        Expr::And(inner) if inner.len() > 1 && terms.len() > 1 => {
+                            format!("({})", t)
+                        }
+                        _ => format!("{}", t),
+                    })
+                    .collect();
+                write!(f, "{}", parts.join(" | "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = src.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                chars.next();
+            }
+            '!' => {
+                chars.next();
+                tokens.push(Token::Not);
+            }
+            '&' => {
+                chars.next();
+                tokens.push(Token::And);
+            }
+            '|' => {
+                chars.next();
+                tokens.push(Token::Or);
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' || c == '.' {
+                        ident.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            _ => return Err(format!("unexpected character '{}'", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, ! binds tighter than & which binds tighter than |
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut terms = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            terms.push(self.parse_and()?);
+        }
+        if terms.len() == 1 {
+            return Ok(terms.pop().unwrap());
+        }
+        Ok(Expr::Or(terms))
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut terms = vec![self.parse_unary()?];
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            terms.push(self.parse_unary()?);
+        }
+        if terms.len() == 1 {
+            return Ok(terms.pop().unwrap());
+        }
+        Ok(Expr::And(terms))
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        let tok = self.peek().cloned();
+        self.pos += 1;
+        match tok {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Open) => {
+                let inner = self.parse_or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err("missing closing bracket".to_string());
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "0" | "false" | "FALSE" => Ok(Expr::Const(false)),
+                "1" | "true" | "TRUE" => Ok(Expr::Const(true)),
+                _ => Ok(Expr::Var(name)),
+            },
+            Some(tok) => Err(format!("unexpected token {:?}", tok)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+/// Parses a boolean expression using the operators ! & | and brackets
+pub fn parse(src: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        pos: 0,
+    };
+    let expr = parser.parse_or()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(format!("unexpected token {:?}", parser.tokens[parser.pos]));
+    }
+    Ok(expr)
+}
+
+/// An implicant covers every table row that agrees with <value> on the bits not set in <mask>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Implicant {
+    value: usize,
+    mask: usize,
+}
+
+impl Implicant {
+    fn covers(&self, row: usize) -> bool {
+        row & !self.mask == self.value
+    }
+}
+
+/// Quine-McCluskey prime implicant generation
+fn prime_implicants(minterms: &[usize]) -> Vec<Implicant> {
+    let mut current: BTreeSet<Implicant> = minterms
+        .iter()
+        .map(|m| Implicant { value: *m, mask: 0 })
+        .collect();
+    let mut primes = BTreeSet::new();
+    while !current.is_empty() {
+        let mut next = BTreeSet::new();
+        let mut merged = BTreeSet::new();
+        let list: Vec<Implicant> = current.iter().cloned().collect();
+        for a in 0..list.len() {
+            for b in (a + 1)..list.len() {
+                let (x, y) = (list[a], list[b]);
+                let diff = x.value ^ y.value;
+                if x.mask == y.mask && diff.count_ones() == 1 {
+                    next.insert(Implicant {
+                        value: x.value & !diff,
+                        mask: x.mask | diff,
+                    });
+                    merged.insert(x);
+                    merged.insert(y);
+                }
+            }
+        }
+        for imp in list {
+            if !merged.contains(&imp) {
+                primes.insert(imp);
+            }
+        }
+        current = next;
+    }
+    primes.into_iter().collect()
+}
+
+/// Finds the smallest set of <primes> covering all <minterms>. Exhaustive search is used while the
+/// number of non-essential primes is small, beyond that a greedy cover is returned
+fn minimal_cover(primes: &[Implicant], minterms: &[usize]) -> Vec<Implicant> {
+    let mut chosen = Vec::<Implicant>::new();
+    // essential primes are the only cover of some minterm
+    for m in minterms {
+        let covering: Vec<&Implicant> = primes.iter().filter(|p| p.covers(*m)).collect();
+        if covering.len() == 1 && !chosen.contains(covering[0]) {
+            chosen.push(*covering[0]);
+        }
+    }
+    let remaining: Vec<usize> = minterms
+        .iter()
+        .cloned()
+        .filter(|m| !chosen.iter().any(|p| p.covers(*m)))
+        .collect();
+    let candidates: Vec<Implicant> = primes
+        .iter()
+        .cloned()
+        .filter(|p| !chosen.contains(p) && remaining.iter().any(|m| p.covers(*m)))
+        .collect();
+    if remaining.is_empty() {
+        chosen.sort();
+        return chosen;
+    }
+    if candidates.len() <= 16 {
+        let mut best: Option<u32> = None;
+        for subset in 1u32..(1 << candidates.len()) {
+            if let Some(b) = best {
+                if subset.count_ones() >= b.count_ones() {
+                    continue;
+                }
+            }
+            let covered = remaining.iter().all(|m| {
+                (0..candidates.len()).any(|c| subset & (1 << c) != 0 && candidates[c].covers(*m))
+            });
+            if covered {
+                best = Some(subset);
+            }
+        }
+        let best = best.expect("Prime implicants do not cover the function");
+        for (c, imp) in candidates.iter().enumerate() {
+            if best & (1 << c) != 0 {
+                chosen.push(*imp);
+            }
+        }
+    } else {
+        let mut uncovered = remaining;
+        while !uncovered.is_empty() {
+            let pick = *candidates
+                .iter()
+                .max_by_key(|p| uncovered.iter().filter(|m| p.covers(**m)).count())
+                .unwrap();
+            uncovered.retain(|m| !pick.covers(*m));
+            chosen.push(pick);
+        }
+    }
+    chosen.sort();
+    chosen
+}
+
+/// Converts a truth table over <inputs> (first input is the least significant bit) into a minimal
+/// sum of products expression. Inputs the function does not depend on do not appear in the result
+pub fn minimal_dnf(tbl: &[bool], inputs: &[String]) -> Expr {
+    if tbl.len() != 1 << inputs.len() {
+        panic!(
+            "Truth table of size {} does not match {} inputs",
+            tbl.len(),
+            inputs.len()
+        );
+    }
+    let minterms: Vec<usize> = (0..tbl.len()).filter(|row| tbl[*row]).collect();
+    if minterms.is_empty() {
+        return Expr::Const(false);
+    }
+    if minterms.len() == tbl.len() {
+        return Expr::Const(true);
+    }
+    let cover = minimal_cover(&prime_implicants(&minterms), &minterms);
+    let mut terms = Vec::new();
+    for imp in cover {
+        let mut literals = Vec::new();
+        for (bit, name) in inputs.iter().enumerate() {
+            if imp.mask & (1 << bit) != 0 {
+                continue;
+            }
+            if imp.value & (1 << bit) != 0 {
+                literals.push(Expr::Var(name.clone()));
+            } else {
+                literals.push(Expr::Not(Box::new(Expr::Var(name.clone()))));
+            }
+        }
+        if literals.len() == 1 {
+            terms.push(literals.pop().unwrap());
+        } else {
+            terms.push(Expr::And(literals));
+        }
+    }
+    if terms.len() == 1 {
+        return terms.pop().unwrap();
+    }
+    Expr::Or(terms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("x{}", i)).collect()
+    }
+
+    #[test]
+    fn parse_precedence() {
+        let e = parse("!a & b | c").unwrap();
+        let expected = Expr::Or(vec![
+            Expr::And(vec![
+                Expr::Not(Box::new(Expr::Var("a".to_string()))),
+                Expr::Var("b".to_string()),
+            ]),
+            Expr::Var("c".to_string()),
+        ]);
+        assert_eq!(e, expected);
+        assert!(parse("a & (b | c").is_err());
+        assert!(parse("a b").is_err());
+    }
+
+    #[test]
+    fn dnf_roundtrip_all_k2() {
+        let inputs = names(2);
+        for f in 0..16usize {
+            let tbl: Vec<bool> = (0..4).map(|row| (f >> row) & 1 == 1).collect();
+            let dnf = minimal_dnf(&tbl, &inputs);
+            let reparsed = parse(&format!("{}", dnf)).unwrap();
+            assert_eq!(reparsed.truth_table(&inputs), tbl, "function {}", dnf);
+        }
+    }
+
+    #[test]
+    fn dnf_is_minimal() {
+        let inputs = names(3);
+        // x0 | x1 & x2
+        let tbl: Vec<bool> = (0..8).map(|row| row & 1 == 1 || row & 6 == 6).collect();
+        assert_eq!(format!("{}", minimal_dnf(&tbl, &inputs)), "x0 | (x1 & x2)");
+        // xor has no simplification
+        let tbl = vec![false, true, true, false];
+        assert_eq!(
+            format!("{}", minimal_dnf(&tbl, &names(2))),
+            "(x0 & !x1) | (!x0 & x1)"
+        );
+        assert_eq!(format!("{}", minimal_dnf(&[true; 4], &names(2))), "1");
+    }
+}
@@ -0,0 +1,149 @@
+//! Native JSON atom format, the truth table and inputs of every Node exactly as they are stored
+//! so nothing is lost on a round trip
+use crate::formats::FormatError;
+use crate::rbn::RBN;
+use crate::util::grouping::SitePolicy;
+use std::fs;
+use std::path::Path;
+
+/// Definition of an atom, Node <i> has truth table <tables[i]> and takes its inputs from the
+/// Nodes in <inputs[i]>, the first input being the least significant bit of the table lookup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtomDef {
+    pub tables: Vec<Vec<bool>>,
+    pub inputs: Vec<Vec<usize>>,
+    /// Candidate (truth table, probability) pairs of a probabilistic Boolean network, Node i
+    /// chooses one of <functions[i]> every step. Missing or empty for ordinary Nodes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub functions: Vec<Vec<(Vec<bool>, f64)>>,
+    /// How the bonding sites are generated, the default policy if missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sites: Option<SitePolicy>,
+}
+
+impl AtomDef {
+    pub fn from_rbn(rbn: &RBN) -> AtomDef {
+        let n = rbn.get_node_count();
+        let functions = if rbn.is_probabilistic() {
+            (0..n).map(|idx| rbn.get_node_functions(idx)).collect()
+        } else {
+            vec![]
+        };
+        AtomDef {
+            tables: (0..n).map(|idx| rbn.get_function_table(idx)).collect(),
+            inputs: (0..n).map(|idx| rbn.get_node_inputs(idx).clone()).collect(),
+            functions,
+            sites: if rbn.get_site_policy().is_default() {
+                None
+            } else {
+                Some(rbn.get_site_policy().clone())
+            },
+        }
+    }
+
+    /// Builds the RBN, checking the definition first so a bad file is an error rather than a
+    /// panic
+    pub fn to_rbn(&self) -> Result<RBN, FormatError> {
+        if self.tables.len() != self.inputs.len() {
+            return Err(FormatError::Invalid(format!(
+                "{} truth tables for {} Nodes",
+                self.tables.len(),
+                self.inputs.len()
+            )));
+        }
+        for (idx, (tbl, inputs)) in self.tables.iter().zip(&self.inputs).enumerate() {
+            if inputs.len() > 7 {
+                return Err(FormatError::Invalid(format!(
+                    "Node {} has {} inputs, at most 7 are supported",
+                    idx,
+                    inputs.len()
+                )));
+            }
+            if tbl.len() != 1 << inputs.len() {
+                return Err(FormatError::Invalid(format!(
+                    "Node {} has {} inputs but a truth table of size {}",
+                    idx,
+                    inputs.len(),
+                    tbl.len()
+                )));
+            }
+            if let Some(src) = inputs.iter().find(|src| **src >= self.inputs.len()) {
+                return Err(FormatError::Invalid(format!(
+                    "Node {} takes input from unknown Node {}",
+                    idx, src
+                )));
+            }
+        }
+        if self.functions.len() > self.tables.len() {
+            return Err(FormatError::Invalid(format!(
+                "functions for {} Nodes but there are only {}",
+                self.functions.len(),
+                self.tables.len()
+            )));
+        }
+        for (idx, fns) in self.functions.iter().enumerate() {
+            if fns.iter().any(|f| f.0.len() != self.tables[idx].len()) {
+                return Err(FormatError::Invalid(format!(
+                    "Node {} has a candidate truth table of the wrong size",
+                    idx
+                )));
+            }
+            let total: f64 = fns.iter().map(|f| f.1).sum();
+            if !fns.is_empty() && ((total - 1.0).abs() > 1e-6 || fns.iter().any(|f| f.1 < 0.0)) {
+                return Err(FormatError::Invalid(format!(
+                    "function probabilities of Node {} do not sum to 1",
+                    idx
+                )));
+            }
+        }
+        if let Some(policy) = &self.sites {
+            policy
+                .check(self.tables.len())
+                .map_err(|e| FormatError::Invalid(e.to_string()))?;
+        }
+        let mut rbn = RBN::new_from_def_k(self.tables.clone(), self.inputs.clone());
+        if let Some(policy) = &self.sites {
+            rbn.set_site_policy(policy.clone());
+        }
+        for (idx, fns) in self.functions.iter().enumerate() {
+            rbn.set_node_functions(idx, fns.clone());
+        }
+        Ok(rbn)
+    }
+}
+
+pub fn from_json(src: &str) -> Result<RBN, FormatError> {
+    serde_json::from_str::<AtomDef>(src)?.to_rbn()
+}
+
+pub fn to_json(rbn: &RBN) -> String {
+    let mut form_string = serde_json::to_string(&AtomDef::from_rbn(rbn)).unwrap();
+    form_string.push('\n');
+    form_string
+}
+
+pub fn read_json<P: AsRef<Path>>(path: P) -> Result<RBN, FormatError> {
+    from_json(&fs::read_to_string(path)?)
+}
+
+pub fn write_json<P: AsRef<Path>>(rbn: &RBN, path: P) -> Result<(), FormatError> {
+    fs::write(path, to_json(rbn))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let src = "{\"tables\":[[false,true],[true,false,false,true]],\"inputs\":[[1],[0,1]]}";
+        let rbn = from_json(src).unwrap();
+        assert_eq!(rbn.get_node_inputs(1), &vec![0, 1]);
+        assert_eq!(to_json(&rbn), format!("{}\n", src));
+        match from_json("{\"tables\":[[true]],\"inputs\":[[0]]}") {
+            Err(FormatError::Invalid(msg)) => assert!(msg.contains("table of size 1")),
+            other => panic!("expected invalid table, got {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Reading and writing RBNs in the file formats used by other Boolean network tools
+pub mod bnet;
+pub mod boolnet;
+pub mod dot;
+pub mod expr;
+pub mod json;
+pub mod raster;
+pub mod spacetime;
+pub mod stg;
+
+use crate::formats::expr::Expr;
+use crate::rbn::RBN;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    /// Syntax or semantic error on line <line> (1 based) of the input
+    Parse {
+        line: usize,
+        msg: String,
+    },
+    /// The input is well formed but does not describe a valid RBN
+    Invalid(String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(err) => write!(f, "{}", err),
+            FormatError::Parse { line, msg } => write!(f, "line {}: {}", line, msg),
+            FormatError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for FormatError {
+    fn from(err: io::Error) -> Self {
+        FormatError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for FormatError {
+    fn from(err: serde_json::Error) -> Self {
+        FormatError::Parse {
+            line: err.line(),
+            msg: err.to_string(),
+        }
+    }
+}
+
+/// Reads an atom in the format given by the extension of <path>: .json, .bnet or .bn (BoolNet)
+pub fn read_atom<P: AsRef<Path>>(path: P) -> Result<RBN, FormatError> {
+    let path = path.as_ref();
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => json::read_json(path),
+        Some("bnet") => bnet::read_bnet(path),
+        Some("bn") => boolnet::read_boolnet(path),
+        _ => Err(FormatError::Invalid(format!(
+            "unknown atom format {}, expected .json, .bnet or .bn",
+            path.display()
+        ))),
+    }
+}
+
+/// Writes an atom in the format given by the extension of <path>, see read_atom
+pub fn write_atom<P: AsRef<Path>>(rbn: &RBN, path: P) -> Result<(), FormatError> {
+    let path = path.as_ref();
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => json::write_json(rbn, path),
+        Some("bnet") => bnet::write_bnet(rbn, path),
+        Some("bn") => boolnet::write_boolnet(rbn, path),
+        _ => Err(FormatError::Invalid(format!(
+            "unknown atom format {}, expected .json, .bnet or .bn",
+            path.display()
+        ))),
+    }
+}
+
+/// Name a Node is given when exported, ids are only unique within one RBN
+pub fn node_name(idx: usize) -> String {
+    format!("n{}", idx)
+}
+
+/// One "target, factors" line of a rule file
+struct Rule {
+    target: String,
+    factors: Expr,
+    line: usize,
+}
+
+/// Splits <src> into rules. Blank lines and # comments are skipped, the "targets, factors" header
+/// is skipped if present and is an error if missing when <header_required>
+fn parse_rules(src: &str, header_required: bool) -> Result<Vec<Rule>, FormatError> {
+    let mut rules = Vec::new();
+    let mut seen_header = false;
+    for (idx, raw) in src.lines().enumerate() {
+        let line = idx + 1;
+        let content = raw.split('#').next().unwrap().trim();
+        if content.is_empty() {
+            continue;
+        }
+        let mut cols = content.splitn(2, ',');
+        let target = cols.next().unwrap().trim();
+        let factors = match cols.next() {
+            Some(f) => f.trim(),
+            None => {
+                return Err(FormatError::Parse {
+                    line,
+                    msg: "expected \"target, factors\"".to_string(),
+                })
+            }
+        };
+        if !seen_header && rules.is_empty() && target.eq_ignore_ascii_case("targets") {
+            if factors.contains(',') {
+                return Err(FormatError::Parse {
+                    line,
+                    msg: "probabilistic networks are not supported".to_string(),
+                });
+            }
+            seen_header = true;
+            continue;
+        }
+        if header_required && !seen_header {
+            return Err(FormatError::Parse {
+                line,
+                msg: "missing \"targets, factors\" header".to_string(),
+            });
+        }
+        let factors = expr::parse(factors).map_err(|msg| FormatError::Parse { line, msg })?;
+        rules.push(Rule {
+            target: target.to_string(),
+            factors,
+            line,
+        });
+    }
+    Ok(rules)
+}
+
+/// Builds an RBN from parsed rules. Node <i> is the target of rule <i>, its inputs are the
+/// variables of its expression in Node order
+fn build_rbn(rules: Vec<Rule>) -> Result<RBN, FormatError> {
+    let mut index = HashMap::<String, usize>::new();
+    for (idx, rule) in rules.iter().enumerate() {
+        if index.insert(rule.target.clone(), idx).is_some() {
+            return Err(FormatError::Parse {
+                line: rule.line,
+                msg: format!("duplicate target {}", rule.target),
+            });
+        }
+    }
+    let mut tbls = Vec::new();
+    let mut strct = Vec::new();
+    for rule in &rules {
+        let mut inputs = Vec::new();
+        for var in rule.factors.variables() {
+            match index.get(&var) {
+                Some(idx) => inputs.push((*idx, var)),
+                None => {
+                    return Err(FormatError::Parse {
+                        line: rule.line,
+                        msg: format!("unknown node {}", var),
+                    })
+                }
+            }
+        }
+        if inputs.len() > 7 {
+            return Err(FormatError::Parse {
+                line: rule.line,
+                msg: format!("{} inputs, at most 7 are supported", inputs.len()),
+            });
+        }
+        inputs.sort();
+        let names: Vec<String> = inputs.iter().map(|i| i.1.clone()).collect();
+        tbls.push(rule.factors.truth_table(&names));
+        strct.push(inputs.iter().map(|i| i.0).collect());
+    }
+    Ok(RBN::new_from_def_k(tbls, strct))
+}
+
+/// Minimal DNF of every Node in <rbn> as (target name, factors) pairs. Repeated inputs are merged
+/// so only the function is preserved, not redundant wiring
+fn export_rules(rbn: &RBN) -> Vec<(String, Expr)> {
+    let mut rules = Vec::new();
+    for idx in 0..rbn.get_node_count() {
+        let inputs = rbn.get_node_inputs(idx);
+        let tbl = rbn.get_function_table(idx);
+        let mut distinct = inputs.clone();
+        distinct.sort();
+        distinct.dedup();
+        // re-express the table over the distinct inputs
+        let mut reduced = Vec::with_capacity(1 << distinct.len());
+        for row in 0..(1usize << distinct.len()) {
+            let mut orig = 0;
+            for (bit, input) in inputs.iter().enumerate() {
+                let pos = distinct.iter().position(|d| d == input).unwrap();
+                if (row >> pos) & 1 == 1 {
+                    orig |= 1 << bit;
+                }
+            }
+            reduced.push(tbl[orig]);
+        }
+        let names: Vec<String> = distinct.iter().map(|d| node_name(*d)).collect();
+        rules.push((node_name(idx), expr::minimal_dnf(&reduced, &names)));
+    }
+    rules
+}
@@ -0,0 +1,172 @@
+//! Minimal RGB raster with PBM and PNG encoders, so images can be written without pulling in an
+//! image library. PNG data is stored uncompressed
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub type Rgb = [u8; 3];
+
+pub const WHITE: Rgb = [255, 255, 255];
+pub const BLACK: Rgb = [0, 0, 0];
+
+pub struct Raster {
+    pub width: usize,
+    pub height: usize,
+    /// Row major pixels, pixels[y * width + x]
+    pub pixels: Vec<Rgb>,
+}
+
+impl Raster {
+    pub fn new(width: usize, height: usize) -> Raster {
+        Raster {
+            width,
+            height,
+            pixels: vec![WHITE; width * height],
+        }
+    }
+
+    /// Fills the <w> by <h> rectangle with top left corner <x>, <y>
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, colour: Rgb) {
+        for row in y..(y + h).min(self.height) {
+            for col in x..(x + w).min(self.width) {
+                self.pixels[row * self.width + col] = colour;
+            }
+        }
+    }
+
+    /// Every row of pixels from the top, empty rows if the raster has no width
+    fn rows(&self) -> impl Iterator<Item = &[Rgb]> {
+        (0..self.height).map(move |y| &self.pixels[y * self.width..(y + 1) * self.width])
+    }
+
+    /// Binary (P4) PBM, pixels darker than mid grey are black
+    pub fn to_pbm(&self) -> Vec<u8> {
+        let mut data = format!("P4\n{} {}\n", self.width, self.height).into_bytes();
+        for row in self.rows() {
+            let mut byte = 0u8;
+            for (col, px) in row.iter().enumerate() {
+                let luma = 299 * px[0] as u32 + 587 * px[1] as u32 + 114 * px[2] as u32;
+                if luma < 128 * 1000 {
+                    byte |= 0x80 >> (col % 8);
+                }
+                // rows are padded to a whole byte
+                if col % 8 == 7 || col + 1 == row.len() {
+                    data.push(byte);
+                    byte = 0;
+                }
+            }
+        }
+        data
+    }
+
+    /// 8 bit truecolour PNG
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&(self.width as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // bit depth 8, colour type 2 (RGB), default compression, filter and no interlace
+        ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+        png_chunk(&mut data, b"IHDR", &ihdr);
+        let mut scanlines = Vec::with_capacity(self.height * (1 + 3 * self.width));
+        for row in self.rows() {
+            scanlines.push(0); // filter type none
+            for px in row {
+                scanlines.extend_from_slice(px);
+            }
+        }
+        png_chunk(&mut data, b"IDAT", &zlib_stored(&scanlines));
+        png_chunk(&mut data, b"IEND", &[]);
+        data
+    }
+
+    pub fn write_pbm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_pbm())
+    }
+
+    pub fn write_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_png())
+    }
+}
+
+fn png_chunk(data: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+    data.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    let start = data.len();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(body);
+    let crc = crc32(&data[start..]);
+    data.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for b in bytes {
+        crc ^= *b as u32;
+        for _bit in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// zlib stream made of uncompressed deflate blocks
+fn zlib_stored(raw: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(0xffff).collect();
+    if blocks.is_empty() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    for (idx, block) in blocks.iter().enumerate() {
+        out.push((idx + 1 == blocks.len()) as u8);
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in raw {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pbm_bits() {
+        let mut img = Raster::new(10, 2);
+        img.fill_rect(0, 0, 1, 1, BLACK);
+        img.fill_rect(9, 1, 1, 1, BLACK);
+        let pbm = img.to_pbm();
+        let header = b"P4\n10 2\n";
+        assert_eq!(&pbm[..header.len()], header);
+        assert_eq!(&pbm[header.len()..], &[0x80, 0x00, 0x00, 0x40]);
+        assert_eq!(Raster::new(0, 2).to_pbm(), b"P4\n0 2\n");
+    }
+
+    #[test]
+    fn png_chunks() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        let png = Raster::new(3, 2).to_png();
+        assert_eq!(&png[..8], &[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a]);
+        assert_eq!(
+            &png[png.len() - 8..],
+            &[b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+        // 2 rows of filter byte + 3 white pixels, stored in one final block
+        let idat_len = u32::from_be_bytes([png[33], png[34], png[35], png[36]]);
+        assert_eq!(idat_len, 2 + 5 + 20 + 4);
+        // an empty raster still has a filter byte for every row
+        let png = Raster::new(0, 2).to_png();
+        let idat_len = u32::from_be_bytes([png[33], png[34], png[35], png[36]]);
+        assert_eq!(idat_len, 2 + 5 + 2 + 4);
+    }
+}
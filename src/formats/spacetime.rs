@@ -0,0 +1,223 @@
+//! Space-time diagrams of recorded trajectories. Rows are time steps from top to bottom, columns
+//! are Nodes with the highest Node on the left (the same order as IsFormatable::fmt_state). A strip
+//! down the left edge marks transient (grey) and cycle (green) rows and a red line separates them
+use crate::formats::raster::{Raster, Rgb, BLACK, WHITE};
+use crate::formats::FormatError;
+use crate::trajectory::Trajectory;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const TRANSIENT_MARK: Rgb = [153, 153, 153];
+const CYCLE_MARK: Rgb = [44, 160, 44];
+const SEPARATOR: Rgb = [214, 39, 40];
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpaceTimeStyle {
+    /// Side of one cell in pixels
+    pub cell: usize,
+    /// Colour the on cells of each Node by its cycle liveliness, blue for mostly off through to
+    /// red for mostly on, instead of black
+    pub liveliness: bool,
+}
+
+impl Default for SpaceTimeStyle {
+    fn default() -> Self {
+        SpaceTimeStyle {
+            cell: 4,
+            liveliness: false,
+        }
+    }
+}
+
+impl SpaceTimeStyle {
+    /// Checks every cell of the diagram covers at least one pixel
+    pub fn check(&self) -> Result<(), FormatError> {
+        if self.cell == 0 {
+            return Err(FormatError::Invalid(
+                "cell size must be at least 1 pixel".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Colour of the on cells of every Node
+fn node_colours(traj: &Trajectory, style: &SpaceTimeStyle) -> Vec<Rgb> {
+    if !style.liveliness || traj.cycle == 0 {
+        return vec![BLACK; traj.node_count()];
+    }
+    traj.cycle_liveliness()
+        .iter()
+        .map(|live| {
+            // live is in -cycle..=cycle, map it onto 0..=255
+            let t = ((*live + traj.cycle as i32) * 255 / (2 * traj.cycle as i32)) as u8;
+            [t, 0, 255 - t]
+        })
+        .collect()
+}
+
+fn hex(colour: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", colour[0], colour[1], colour[2])
+}
+
+/// Draws the diagram into a raster, one pixel row is added between transient and cycle for the
+/// separator if there is a transient
+pub fn to_raster(traj: &Trajectory, style: &SpaceTimeStyle) -> Raster {
+    let n = traj.node_count();
+    let sep = if traj.transient > 0 { 1 } else { 0 };
+    let mut img = Raster::new((n + 1) * style.cell, traj.states.len() * style.cell + sep);
+    let colours = node_colours(traj, style);
+    for (t, row) in traj.states.iter().enumerate() {
+        let y = t * style.cell + if t >= traj.transient { sep } else { 0 };
+        let mark = if t < traj.transient {
+            TRANSIENT_MARK
+        } else {
+            CYCLE_MARK
+        };
+        img.fill_rect(0, y, style.cell, style.cell, mark);
+        for (idx, val) in row.iter().enumerate() {
+            let x = (n - idx) * style.cell;
+            let colour = if *val { colours[idx] } else { WHITE };
+            img.fill_rect(x, y, style.cell, style.cell, colour);
+        }
+    }
+    if sep == 1 {
+        img.fill_rect(0, traj.transient * style.cell, img.width, 1, SEPARATOR);
+    }
+    img
+}
+
+/// Black and white PBM, liveliness colouring is ignored
+pub fn to_pbm(traj: &Trajectory, style: &SpaceTimeStyle) -> Vec<u8> {
+    let plain = SpaceTimeStyle {
+        liveliness: false,
+        ..*style
+    };
+    to_raster(traj, &plain).to_pbm()
+}
+
+pub fn to_png(traj: &Trajectory, style: &SpaceTimeStyle) -> Vec<u8> {
+    to_raster(traj, style).to_png()
+}
+
+pub fn to_svg(traj: &Trajectory, style: &SpaceTimeStyle) -> String {
+    let n = traj.node_count();
+    let c = style.cell;
+    let colours = node_colours(traj, style);
+    let mut form_string = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" shape-rendering=\"crispEdges\">\n",
+        (n + 1) * c,
+        traj.states.len() * c
+    );
+    form_string.push_str(&format!(
+        "<title>transient {}, cycle {}</title>\n<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+        traj.transient, traj.cycle
+    ));
+    for (t, row) in traj.states.iter().enumerate() {
+        let mark = if t < traj.transient {
+            TRANSIENT_MARK
+        } else {
+            CYCLE_MARK
+        };
+        form_string.push_str(&format!(
+            "<rect x=\"0\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+            t * c,
+            c,
+            c,
+            hex(mark)
+        ));
+        for (idx, val) in row.iter().enumerate() {
+            if *val {
+                form_string.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                    (n - idx) * c,
+                    t * c,
+                    c,
+                    c,
+                    hex(colours[idx])
+                ));
+            }
+        }
+    }
+    if traj.transient > 0 {
+        form_string.push_str(&format!(
+            "<line x1=\"0\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\"/>\n",
+            traj.transient * c,
+            (n + 1) * c,
+            traj.transient * c,
+            hex(SEPARATOR)
+        ));
+    }
+    form_string.push_str("</svg>\n");
+    form_string
+}
+
+pub fn write_svg<P: AsRef<Path>>(
+    traj: &Trajectory,
+    style: &SpaceTimeStyle,
+    path: P,
+) -> io::Result<()> {
+    fs::write(path, to_svg(traj, style))
+}
+
+pub fn write_pbm<P: AsRef<Path>>(
+    traj: &Trajectory,
+    style: &SpaceTimeStyle,
+    path: P,
+) -> io::Result<()> {
+    fs::write(path, to_pbm(traj, style))
+}
+
+pub fn write_png<P: AsRef<Path>>(
+    traj: &Trajectory,
+    style: &SpaceTimeStyle,
+    path: P,
+) -> io::Result<()> {
+    fs::write(path, to_png(traj, style))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn traj() -> Trajectory {
+        Trajectory {
+            states: vec![vec![true, true], vec![false, true], vec![true, true]],
+            transient: 1,
+            cycle: 2,
+        }
+    }
+
+    #[test]
+    fn spacetime_raster() {
+        let style = SpaceTimeStyle {
+            cell: 1,
+            liveliness: true,
+        };
+        assert!(style.check().is_ok());
+        let img = to_raster(&traj(), &style);
+        assert_eq!((img.width, img.height), (3, 4));
+        // Node 1 is always on so its liveliness is +cycle and it is fully red
+        assert_eq!(img.pixels[1], [255, 0, 0]);
+        // Node 0 is on half the cycle
+        assert_eq!(img.pixels[3 * 3 + 2], [127, 0, 128]);
+        assert_eq!(img.pixels[3], SEPARATOR);
+        assert_eq!(img.pixels[3 * 2], CYCLE_MARK);
+        assert_eq!(img.pixels[0], TRANSIENT_MARK);
+
+        // cells of no pixels are refused, though drawing them still gives an empty image
+        let empty = SpaceTimeStyle { cell: 0, ..style };
+        assert!(empty.check().is_err());
+        assert_eq!(to_pbm(&traj(), &empty), b"P4\n0 1\n");
+    }
+
+    #[test]
+    fn spacetime_svg() {
+        let svg = to_svg(&traj(), &SpaceTimeStyle::default());
+        assert!(svg.contains("<title>transient 1, cycle 2</title>"));
+        // 3 row marks and 5 on cells
+        assert_eq!(svg.matches("<rect x=").count(), 8);
+        assert!(svg.contains("<line x1=\"0\" y1=\"4\""));
+    }
+}
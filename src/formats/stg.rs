@@ -0,0 +1,190 @@
+//! State transition graph export in DOT and GraphML. States are labelled by their Node values with
+//! the highest Node first, the same order as IsFormatable::fmt_state
+use super::dot::DOT_PALETTE;
+use crate::state_space::StateSpace;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn state_label(space: &StateSpace, state: u64) -> String {
+    format!("{:0width$b}", state, width = space.n)
+}
+
+/// Number of predecessors of every state
+fn in_degree(space: &StateSpace) -> Vec<usize> {
+    let mut deg = vec![0; space.successor.len()];
+    for next in &space.successor {
+        deg[*next as usize] += 1;
+    }
+    deg
+}
+
+/// Node size hint, states many others flow into are drawn larger
+fn state_size(deg: usize) -> f64 {
+    0.3 + 0.1 * (1.0 + deg as f64).ln()
+}
+
+/// Each basin is a cluster labelled with its attractor's cycle length and basin size. Cycle states
+/// are double circles, garden of eden states (no predecessors) are dashed. If <start> is given
+/// its path to the attractor is drawn in red
+pub fn to_dot(space: &StateSpace, start: Option<u64>) -> String {
+    let deg = in_degree(space);
+    let path = start
+        .map(|s| space.path_to_attractor(s))
+        .unwrap_or_default();
+    let mut form_string = String::new();
+    form_string.push_str("digraph stg {\ngraph [rankdir=LR, overlap=false, splines=true];\n");
+    form_string.push_str("node [shape=circle, style=filled, fontsize=8];\n");
+    for (att, cycle) in space.attractors.iter().enumerate() {
+        form_string.push_str(&format!(
+            "subgraph cluster_a{} {{\nlabel=\"attractor {}: cycle {}, basin {}\";\n",
+            att,
+            att,
+            cycle.len(),
+            space.basin_size[att]
+        ));
+        let colour = DOT_PALETTE[att % DOT_PALETTE.len()];
+        for state in 0..space.successor.len() {
+            if space.attractor[state] != att {
+                continue;
+            }
+            let state = state as u64;
+            let mut attrs = format!(
+                "label=\"{}\", fillcolor=\"{}\", width={:.2}",
+                state_label(space, state),
+                colour,
+                state_size(deg[state as usize])
+            );
+            if space.is_cycle_state(state) {
+                attrs.push_str(", shape=doublecircle");
+            } else if deg[state as usize] == 0 {
+                attrs.push_str(", style=\"filled,dashed\"");
+            }
+            if path.contains(&state) {
+                attrs.push_str(", color=red, penwidth=2");
+            }
+            form_string.push_str(&format!("s{} [{}];\n", state, attrs));
+        }
+        form_string.push_str("}\n");
+    }
+    for (state, next) in space.successor.iter().enumerate() {
+        let on_path = path
+            .windows(2)
+            .any(|w| w[0] == state as u64 && w[1] == *next);
+        if on_path {
+            form_string.push_str(&format!(
+                "s{} -> s{} [color=red, penwidth=2];\n",
+                state, next
+            ));
+        } else {
+            form_string.push_str(&format!("s{} -> s{};\n", state, next));
+        }
+    }
+    form_string.push_str("}\n");
+    form_string
+}
+
+/// GraphML with attractor, cycle, depth, basin size and a size hint on each state. If <start> is
+/// given the edges of its path to the attractor have on_path set
+pub fn to_graphml(space: &StateSpace, start: Option<u64>) -> String {
+    let deg = in_degree(space);
+    let path = start
+        .map(|s| space.path_to_attractor(s))
+        .unwrap_or_default();
+    let mut form_string = String::new();
+    form_string.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    form_string.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    for (id, for_what, ty) in &[
+        ("label", "node", "string"),
+        ("attractor", "node", "int"),
+        ("cycle", "node", "boolean"),
+        ("depth", "node", "int"),
+        ("basin_size", "node", "int"),
+        ("size", "node", "double"),
+        ("on_path", "edge", "boolean"),
+    ] {
+        form_string.push_str(&format!(
+            "<key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>\n",
+            id, for_what, id, ty
+        ));
+    }
+    form_string.push_str("<graph id=\"stg\" edgedefault=\"directed\">\n");
+    for (state, state_deg) in deg.iter().enumerate() {
+        let att = space.attractor[state];
+        form_string.push_str(&format!(
+            "<node id=\"s{}\"><data key=\"label\">{}</data><data key=\"attractor\">{}</data>\
+             <data key=\"cycle\">{}</data><data key=\"depth\">{}</data>\
+             <data key=\"basin_size\">{}</data><data key=\"size\">{:.2}</data></node>\n",
+            state,
+            state_label(space, state as u64),
+            att,
+            space.is_cycle_state(state as u64),
+            space.depth[state],
+            space.basin_size[att],
+            state_size(*state_deg)
+        ));
+    }
+    for (state, next) in space.successor.iter().enumerate() {
+        let on_path = path
+            .windows(2)
+            .any(|w| w[0] == state as u64 && w[1] == *next);
+        form_string.push_str(&format!(
+            "<edge source=\"s{}\" target=\"s{}\"><data key=\"on_path\">{}</data></edge>\n",
+            state, next, on_path
+        ));
+    }
+    form_string.push_str("</graph>\n</graphml>\n");
+    form_string
+}
+
+pub fn write_dot<P: AsRef<Path>>(
+    space: &StateSpace,
+    start: Option<u64>,
+    path: P,
+) -> io::Result<()> {
+    fs::write(path, to_dot(space, start))
+}
+
+pub fn write_graphml<P: AsRef<Path>>(
+    space: &StateSpace,
+    start: Option<u64>,
+    path: P,
+) -> io::Result<()> {
+    fs::write(path, to_graphml(space, start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbn::RBN;
+
+    fn space() -> StateSpace {
+        // n0 = !n1, n1 = n0 gives a single cycle of length 4, n2 = 0 makes half the states
+        // transient
+        let tbls = vec![vec![true, false], vec![false, true], vec![false, false]];
+        let rbn = RBN::new_from_def_k(tbls, vec![vec![1], vec![0], vec![2]]);
+        StateSpace::from_rbn(&rbn)
+    }
+
+    #[test]
+    fn stg_dot() {
+        let space = space();
+        assert_eq!(space.attractors.len(), 1);
+        let dot = to_dot(&space, Some(4));
+        assert!(dot.contains("label=\"attractor 0: cycle 4, basin 8\""));
+        assert!(dot.contains("s0 [label=\"000\""));
+        assert!(dot.contains("s4 [label=\"100\""));
+        assert!(dot.contains("s4 -> s1 [color=red, penwidth=2];\n"));
+        assert_eq!(dot.matches(" -> ").count(), 8);
+    }
+
+    #[test]
+    fn stg_graphml() {
+        let space = space();
+        let xml = to_graphml(&space, None);
+        assert_eq!(xml.matches("<node ").count(), 8);
+        assert_eq!(xml.matches("<edge ").count(), 8);
+        assert!(xml.contains("<node id=\"s4\"><data key=\"label\">100</data>"));
+        assert!(xml.contains("<data key=\"depth\">1</data>"));
+    }
+}
@@ -0,0 +1,26 @@
+extern crate bit_field;
+extern crate rand;
+extern crate rand_pcg;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate toml;
+
+pub mod config;
+pub mod ensemble;
+pub mod evolve;
+pub mod experiment;
+pub mod formats;
+pub mod node;
+pub mod noise;
+pub mod particle;
+pub mod pbn;
+pub mod periodic;
+pub mod perturb;
+pub mod rbn;
+pub mod reactor;
+pub mod state_space;
+pub mod temp;
+pub mod trajectory;
+pub mod util;
@@ -1,174 +1,984 @@
-extern crate bit_field;
-extern crate rand;
-
-use std::cell::{RefCell, RefMut};
-use std::fmt;
-use std::rc::Rc;
-mod node;
-mod particle;
-mod rbn;
-pub mod temp;
-pub mod util;
-use crate::particle::*;
-use crate::util::bonding::{IsBondable, IsSubSymbolic};
-use std::borrow::Borrow;
+#[macro_use]
+extern crate clap;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate spiky;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use serde::Serialize;
+use spiky::config;
+use spiky::ensemble::{EnsembleCheckpoint, EnsembleConfig};
+use spiky::evolve::{Evolution, EvolveConfig};
+use spiky::experiment::ExperimentSpec;
+use spiky::formats::json::AtomDef;
+use spiky::formats::{self, bnet, boolnet, json};
+use spiky::noise;
+use spiky::pbn::{self, Stationary};
+use spiky::periodic::{self, PeriodicConfig, PeriodicTable};
+use spiky::perturb;
+use spiky::rbn::RBN;
+use spiky::reactor::catalysis;
+use spiky::reactor::lattice::{Lattice, LatticeCheckpoint, LatticeConfig};
+use spiky::reactor::network::ReactionNetwork;
+use spiky::reactor::stats::{Sampler, StatsWriter};
+use spiky::reactor::{Reactor, ReactorCheckpoint, ReactorConfig};
+use spiky::state_space::{StateSpace, MAX_STATE_SPACE_NODES};
+use spiky::temp::{self, Temperature};
+use spiky::util::bonding::{IsBondable, IsSubSymbolic};
+use spiky::util::grouping::{Grouping, SmallGroups};
+use spiky::util::recorder::{NullRecorder, Phase};
+use spiky::util::rng::seeded;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::process;
+
+#[derive(Serialize)]
+struct Liveliness {
+    transient: Vec<i32>,
+    cycle: Vec<i32>,
+}
+
+#[derive(Serialize)]
+struct Attractor {
+    /// Cycle states with Node i as bit i
+    states: Vec<u64>,
+    basin_size: usize,
+}
+
+#[derive(Serialize)]
+struct Analysis {
+    nodes: usize,
+    temperature: Temperature,
+    cycle: u64,
+    transient: u64,
+    liveliness: Liveliness,
+    /// Only present for atoms small enough to enumerate every state
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attractors: Option<Vec<Attractor>>,
+}
+
+#[derive(Serialize)]
+struct Best<'a> {
+    fitness: f64,
+    atom: &'a AtomDef,
+}
+
+#[derive(Serialize)]
+struct StationaryState {
+    state: Vec<bool>,
+    probability: f64,
+}
+
+#[derive(Serialize)]
+struct StationaryOutput {
+    #[serde(flatten)]
+    stationary: Stationary,
+    /// Most visited states, most likely first
+    states: Vec<StationaryState>,
+}
+
+#[derive(Serialize)]
+struct Site {
+    nodes: Vec<u16>,
+    spike: Option<i32>,
+}
+
+/// The command line, every subcommand and its arguments
+fn app<'a, 'b>() -> App<'a, 'b> {
+    let temperature = Arg::with_name("temperature")
+        .long("temperature")
+        .short("t")
+        .takes_value(true)
+        .default_value("0")
+        .help("Initial state the atom is calculated from, Node i is bit i");
+    App::new("spiky")
+        .version(crate_version!())
+        .about("Random Boolean network atoms and the chemistry they make")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("generate")
+                .about("Generates a random atom")
+                .arg(
+                    Arg::with_name("n")
+                        .short("n")
+                        .takes_value(true)
+                        .default_value("12")
+                        .help("Number of Nodes"),
+                )
+                .arg(
+                    Arg::with_name("k")
+                        .short("k")
+                        .takes_value(true)
+                        .default_value("2")
+                        .help("Inputs per Node"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .short("s")
+                        .takes_value(true)
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::with_name("bias")
+                        .long("bias")
+                        .short("p")
+                        .takes_value(true)
+                        .default_value("0.5")
+                        .help("Probability of each truth table entry being true"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .short("f")
+                        .takes_value(true)
+                        .possible_values(&["json", "bnet", "bn"])
+                        .default_value("json")
+                        .help("Output format when writing to stdout"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .help("File to write, the format is taken from the extension"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("analyse")
+                .about("Cycle, transient, liveliness and attractors of an atom as JSON")
+                .arg(
+                    Arg::with_name("atom")
+                        .required(true)
+                        .help(".json, .bnet or .bn atom file"),
+                )
+                .arg(temperature.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("stationary")
+                .about("Monte Carlo estimate of the stationary distribution of a probabilistic atom as JSON")
+                .arg(
+                    Arg::with_name("atom")
+                        .required(true)
+                        .help(".json atom file, Nodes may list candidate functions"),
+                )
+                .arg(temperature.clone())
+                .arg(
+                    Arg::with_name("burn-in")
+                        .long("burn-in")
+                        .takes_value(true)
+                        .default_value("1000")
+                        .help("Steps run before any state is counted"),
+                )
+                .arg(
+                    Arg::with_name("steps")
+                        .long("steps")
+                        .takes_value(true)
+                        .default_value("100000")
+                        .help("Steps counted"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .short("s")
+                        .takes_value(true)
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::with_name("top")
+                        .long("top")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help("Most likely states to list"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("noise")
+                .about("Robustness of an atom's attractor when every Node flips with probability eta each step, as JSON")
+                .arg(
+                    Arg::with_name("atom")
+                        .required(true)
+                        .help(".json, .bnet or .bn atom file"),
+                )
+                .arg(temperature.clone())
+                .arg(
+                    Arg::with_name("eta")
+                        .long("eta")
+                        .takes_value(true)
+                        .default_value("0.01")
+                        .help("Probability of each Node flipping each step"),
+                )
+                .arg(
+                    Arg::with_name("steps")
+                        .long("steps")
+                        .takes_value(true)
+                        .default_value("100000")
+                        .help("Noisy steps run from the attractor"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .short("s")
+                        .takes_value(true)
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("perturb")
+                .about("Clamps, state flips and truth table flips of every Node, one JSON line each")
+                .arg(
+                    Arg::with_name("atom")
+                        .required(true)
+                        .help(".json, .bnet or .bn atom file"),
+                )
+                .arg(temperature.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("sites")
+                .about("Interaction groups of an atom and their spikes as JSON")
+                .arg(
+                    Arg::with_name("atom")
+                        .required(true)
+                        .help(".json, .bnet or .bn atom file"),
+                )
+                .arg(temperature)
+                .arg(
+                    Arg::with_name("size")
+                        .long("size")
+                        .takes_value(true)
+                        .help("Largest interaction group, defaults to the number of Nodes"),
+                )
+                .arg(
+                    Arg::with_name("strategy")
+                        .long("strategy")
+                        .takes_value(true)
+                        .possible_values(&["influence", "out-degree", "random-walk", "liveliness", "partitions"])
+                        .default_value("influence")
+                        .help("How the interaction groups are grown"),
+                )
+                .arg(
+                    Arg::with_name("order")
+                        .long("order")
+                        .takes_value(true)
+                        .possible_values(&["most", "least"])
+                        .default_value("most")
+                        .help("Follow the most or least influential, connected or lively Nodes first"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .short("s")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Seed of the random-walk strategy"),
+                )
+                .arg(
+                    Arg::with_name("min-size")
+                        .long("min-size")
+                        .takes_value(true)
+                        .help("Smallest interaction group, smaller ones are merged or discarded"),
+                )
+                .arg(
+                    Arg::with_name("small")
+                        .long("small")
+                        .takes_value(true)
+                        .possible_values(&["merge", "discard"])
+                        .help("What happens to groups below --min-size, merge if not given"),
+                )
+                .arg(
+                    Arg::with_name("overlap")
+                        .long("overlap")
+                        .help("Let groups run on into Nodes already in other groups"),
+                )
+                .arg(
+                    Arg::with_name("sites")
+                        .long("sites")
+                        .takes_value(true)
+                        .help("Number of bonding sites wanted"),
+                )
+                .arg(
+                    Arg::with_name("groups")
+                        .long("groups")
+                        .takes_value(true)
+                        .required_if("strategy", "partitions")
+                        .help("Node indexes of each group for the partitions strategy, e.g. 0,1;2,3"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("react")
+                .about("Runs a reactor and prints a JSON summary")
+                .arg(
+                    Arg::with_name("config")
+                        .required_unless("resume")
+                        .help(".toml or .json reactor configuration"),
+                )
+                .args(&checkpoint_args())
+                .args(&stats_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("lattice")
+                .about("Runs a 2D lattice reactor and prints a JSON summary")
+                .arg(
+                    Arg::with_name("config")
+                        .required_unless("resume")
+                        .help(".toml or .json lattice configuration"),
+                )
+                .arg(
+                    Arg::with_name("snapshots")
+                        .long("snapshots")
+                        .takes_value(true)
+                        .help("File to write the population of every cell to, one JSON line per snapshot"),
+                )
+                .arg(
+                    Arg::with_name("snapshot-every")
+                        .long("snapshot-every")
+                        .takes_value(true)
+                        .requires("snapshots")
+                        .help("Iterations between snapshots, 1000 if not given"),
+                )
+                .args(&checkpoint_args())
+                .args(&stats_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("catalysis")
+                .about("Tries every reaction between a reactor's species alone and with each species as a catalyst, printing a JSON report")
+                .arg(
+                    Arg::with_name("config")
+                        .required(true)
+                        .help(".toml or .json reactor configuration"),
+                )
+                .arg(
+                    Arg::with_name("network")
+                        .long("network")
+                        .takes_value(true)
+                        .help("File to write the reaction network to, DOT for a .dot file and JSON otherwise"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("periodic")
+                .about("Classes random atoms by the spikes of their bonding sites and tabulates which classes bond, printing a JSON report")
+                .arg(
+                    Arg::with_name("config")
+                        .required(true)
+                        .help(".toml or .json periodic table configuration"),
+                )
+                .arg(
+                    Arg::with_name("csv")
+                        .long("csv")
+                        .takes_value(true)
+                        .help("File to write the bonding compatibility matrix to as CSV"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("experiment")
+                .about("Runs a parameter sweep and writes the results table as CSV")
+                .arg(
+                    Arg::with_name("spec")
+                        .required(true)
+                        .help(".toml or .json experiment spec"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .help("File to write instead of stdout"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("ensemble")
+                .about("Attractor, cycle and transient statistics of random networks, one JSON line per n and k")
+                .arg(
+                    Arg::with_name("config")
+                        .required_unless("resume")
+                        .help(".toml or .json ensemble configuration"),
+                )
+                .args(&checkpoint_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("evolve")
+                .about("Searches for atoms with target properties, one JSON line per generation followed by the best atom")
+                .arg(
+                    Arg::with_name("config")
+                        .required(true)
+                        .help(".toml or .json search configuration"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .help("File to write the best atom to, the format is taken from the extension"),
+                ),
+        )
+}
 
 fn main() {
-    let mut nds = Vec::new();
-
-    let mut tbl = Vec::new();
-    tbl.push(true);
-    tbl.push(false);
-    tbl.push(false);
-    tbl.push(true);
-    nds.push(tbl);
-
-    let mut tbl = Vec::new();
-    tbl.push(true);
-    tbl.push(true);
-    tbl.push(false);
-    tbl.push(false);
-    nds.push(tbl);
-
-    let mut tbl = Vec::new();
-    tbl.push(true);
-    tbl.push(false);
-    tbl.push(false);
-    tbl.push(false);
-    nds.push(tbl);
-
-    let mut tbl = Vec::new();
-    tbl.push(true);
-    tbl.push(false);
-    tbl.push(true);
-    tbl.push(true);
-    nds.push(tbl);
-
-    let mut tbl = Vec::new();
-    tbl.push(true);
-    tbl.push(true);
-    tbl.push(false);
-    tbl.push(false);
-    nds.push(tbl);
-
-    let mut tbl = Vec::new();
-    tbl.push(false);
-    tbl.push(false);
-    tbl.push(false);
-    tbl.push(false);
-    nds.push(tbl);
-
-    let mut tbl = Vec::new();
-    tbl.push(true);
-    tbl.push(true);
-    tbl.push(true);
-    tbl.push(false);
-    nds.push(tbl);
-
-    let mut tbl = Vec::new();
-    tbl.push(false);
-    tbl.push(true);
-    tbl.push(true);
-    tbl.push(false);
-    nds.push(tbl);
-
-    let mut tbl = Vec::new();
-    tbl.push(true);
-    tbl.push(false);
-    tbl.push(false);
-    tbl.push(false);
-    nds.push(tbl);
-
-    let mut tbl = Vec::new();
-    tbl.push(false);
-    tbl.push(false);
-    tbl.push(true);
-    tbl.push(false);
-    nds.push(tbl);
-
-    let mut tbl = Vec::new();
-    tbl.push(false);
-    tbl.push(true);
-    tbl.push(true);
-    tbl.push(false);
-    nds.push(tbl);
-
-    let mut tbl = Vec::new();
-    tbl.push(true);
-    tbl.push(false);
-    tbl.push(true);
-    tbl.push(true);
-    nds.push(tbl);
-
-    let mut rbn_struct = Vec::<(usize, usize)>::new();
-    rbn_struct.push((4, 5));
-    rbn_struct.push((3, 5));
-    rbn_struct.push((0, 10));
-    rbn_struct.push((1, 4));
-    rbn_struct.push((3, 4));
-    rbn_struct.push((4, 6));
-    rbn_struct.push((4, 8));
-    rbn_struct.push((11, 4));
-    rbn_struct.push((2, 3));
-    rbn_struct.push((2, 11));
-    rbn_struct.push((0, 5));
-    rbn_struct.push((9, 8));
-    // let mut newrbn = rbn::RBN::new(2, 12);
-    let mut newrbn = rbn::RBN::new_from_def(nds, rbn_struct);
-    newrbn.calculate_particle(0, true);
-    print!("{}", newrbn);
-    newrbn.generate_bonding_sites();
-    println!("---------------------------------------");
-    newrbn.generate_bonding_sites();
-    let mut atom = particle::Particle {
-        bonding_sites: newrbn.generate_bonding_sites(),
-        components: vec![Rc::new(RefCell::new(newrbn))],
+    let matches = app().get_matches();
+    let result = match matches.subcommand() {
+        ("generate", Some(sub)) => generate(sub),
+        ("analyse", Some(sub)) => analyse(sub),
+        ("stationary", Some(sub)) => stationary(sub),
+        ("noise", Some(sub)) => noise(sub),
+        ("perturb", Some(sub)) => perturb(sub),
+        ("sites", Some(sub)) => sites(sub),
+        ("react", Some(sub)) => react(sub),
+        ("lattice", Some(sub)) => lattice(sub),
+        ("catalysis", Some(sub)) => catalysis(sub),
+        ("periodic", Some(sub)) => periodic(sub),
+        ("experiment", Some(sub)) => experiment(sub),
+        ("ensemble", Some(sub)) => ensemble(sub),
+        ("evolve", Some(sub)) => evolve(sub),
+        _ => unreachable!(),
+    };
+    if let Err(msg) = result {
+        eprintln!("spiky: {}", msg);
+        process::exit(1);
+    }
+}
+
+/// Arguments of the subcommands that can checkpoint and resume long runs
+fn checkpoint_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("checkpoint")
+            .long("checkpoint")
+            .takes_value(true)
+            .help("File the state of the run is saved to"),
+        Arg::with_name("every")
+            .long("every")
+            .takes_value(true)
+            .requires("checkpoint")
+            .help("Iterations or networks between checkpoints, 1000 if not given"),
+        Arg::with_name("resume")
+            .long("resume")
+            .takes_value(true)
+            .conflicts_with("config")
+            .help("Checkpoint to carry on from instead of starting from a configuration"),
+    ]
+}
+
+/// Arguments of the subcommands that write population statistics as CSV
+fn stats_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("stats")
+            .long("stats")
+            .takes_value(true)
+            .help("File to write the population time series to"),
+        Arg::with_name("species")
+            .long("species")
+            .takes_value(true)
+            .help("File to write the count of every species to"),
+        Arg::with_name("sizes")
+            .long("sizes")
+            .takes_value(true)
+            .help("File to write the particle size distribution to"),
+        Arg::with_name("interval")
+            .long("interval")
+            .takes_value(true)
+            .help("Iterations between statistics samples, 1000 if not given"),
+    ]
+}
+
+type Tables = StatsWriter<BufWriter<File>>;
+
+/// Opens the statistics tables asked for, None if there are none
+fn stats_writer(args: &ArgMatches) -> Result<Option<Tables>, String> {
+    let mut files = vec![];
+    for name in &["stats", "species", "sizes"] {
+        files.push(match args.value_of(name) {
+            Some(path) => Some(BufWriter::new(
+                File::create(path).map_err(|e| format!("{}: {}", path, e))?,
+            )),
+            None => None,
+        });
+    }
+    if files.iter().all(|f| f.is_none()) {
+        return Ok(None);
+    }
+    let sizes = files.pop().unwrap();
+    let species = files.pop().unwrap();
+    let stats = files.pop().unwrap();
+    StatsWriter::new(stats, species, sizes)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+/// Statistics interval, 1000 unless --interval is given
+fn interval(args: &ArgMatches) -> Result<u64, String> {
+    match args.value_of("interval") {
+        Some(_) => value(args, "interval"),
+        None => Ok(1000),
+    }
+}
+
+/// True if an output written every <every> iterations is due after <iteration> of a run to <end>,
+/// the last iteration always is
+fn due(iteration: u64, end: u64, every: u64) -> bool {
+    iteration >= end || (every > 0 && iteration.is_multiple_of(every))
+}
+
+/// Checkpoint interval, 1000 unless --every is given
+fn every<T: std::str::FromStr + From<u16>>(args: &ArgMatches) -> Result<T, String> {
+    match args.value_of("every") {
+        Some(_) => value(args, "every"),
+        None => Ok(T::from(1000)),
+    }
+}
+
+/// Parses argument <name>, which must have a value
+fn value<T: std::str::FromStr>(args: &ArgMatches, name: &str) -> Result<T, String> {
+    let raw = args.value_of(name).unwrap();
+    raw.parse()
+        .map_err(|_| format!("invalid value {} for {}", raw, name))
+}
+
+fn print_json<T: Serialize>(val: &T) -> Result<(), String> {
+    let line = serde_json::to_string(val).map_err(|e| e.to_string())?;
+    writeln!(io::stdout(), "{}", line).map_err(|e| e.to_string())
+}
+
+fn read_atom(args: &ArgMatches) -> Result<RBN, String> {
+    let path = args.value_of("atom").unwrap();
+    formats::read_atom(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn generate(args: &ArgMatches) -> Result<(), String> {
+    let n: u16 = value(args, "n")?;
+    let k: u8 = value(args, "k")?;
+    let bias: f64 = value(args, "bias")?;
+    config::check_atom_params(n, k, bias).map_err(|e| e.to_string())?;
+    let rbn = RBN::new_with_rng(k, n, bias, &mut seeded(value(args, "seed")?));
+    match args.value_of("output") {
+        Some(path) => formats::write_atom(&rbn, path).map_err(|e| format!("{}: {}", path, e)),
+        None => {
+            let out = match args.value_of("format").unwrap() {
+                "bnet" => bnet::to_bnet(&rbn),
+                "bn" => boolnet::to_boolnet(&rbn),
+                _ => json::to_json(&rbn),
+            };
+            print!("{}", out);
+            Ok(())
+        }
+    }
+}
+
+fn analyse(args: &ArgMatches) -> Result<(), String> {
+    let mut rbn = read_atom(args)?;
+    if rbn.is_probabilistic() {
+        return Err(
+            "the atom is probabilistic and has no single attractor, use stationary".to_string(),
+        );
+    }
+    let temperature: Temperature = value(args, "temperature")?;
+    let (cycle, transient) = rbn
+        .calculate_particle(temperature, &mut NullRecorder)
+        .lengths();
+    let liveliness = Liveliness {
+        transient: rbn.get_liveliness(Phase::Transient),
+        cycle: rbn.get_liveliness(Phase::Cycle),
+    };
+    let attractors = if rbn.get_node_count() <= MAX_STATE_SPACE_NODES {
+        let space = StateSpace::from_rbn(&rbn);
+        Some(
+            space
+                .attractors
+                .iter()
+                .zip(&space.basin_size)
+                .map(|(states, basin_size)| Attractor {
+                    states: states.clone(),
+                    basin_size: *basin_size,
+                })
+                .collect(),
+        )
+    } else {
+        None
+    };
+    print_json(&Analysis {
+        nodes: rbn.get_node_count(),
+        temperature,
+        cycle,
+        transient,
+        liveliness,
+        attractors,
+    })
+}
+
+fn stationary(args: &ArgMatches) -> Result<(), String> {
+    let rbn = read_atom(args)?;
+    let init = temp::bits(value(args, "temperature")?, rbn.get_node_count());
+    let stationary = pbn::estimate(
+        &rbn,
+        &init,
+        value(args, "burn-in")?,
+        value(args, "steps")?,
+        &mut seeded(value(args, "seed")?),
+    );
+    let states = stationary
+        .top(value(args, "top")?)
+        .into_iter()
+        .map(|(state, probability)| StationaryState { state, probability })
+        .collect();
+    print_json(&StationaryOutput { stationary, states })
+}
+
+fn noise(args: &ArgMatches) -> Result<(), String> {
+    let rbn = read_atom(args)?;
+    let eta: f64 = value(args, "eta")?;
+    if !(0.0..=1.0).contains(&eta) {
+        return Err(format!("eta {} is not a probability", eta));
+    }
+    print_json(&noise::robustness(
+        &rbn,
+        value(args, "temperature")?,
+        eta,
+        value(args, "steps")?,
+        &mut seeded(value(args, "seed")?),
+    ))
+}
+
+fn perturb(args: &ArgMatches) -> Result<(), String> {
+    let rbn = read_atom(args)?;
+    if rbn.is_probabilistic() {
+        return Err(
+            "the atom is probabilistic and has no single attractor, use stationary".to_string(),
+        );
+    }
+    for trial in perturb::scan(&rbn, value(args, "temperature")?) {
+        print_json(&trial)?;
+    }
+    Ok(())
+}
+
+fn sites(args: &ArgMatches) -> Result<(), String> {
+    let mut rbn = read_atom(args)?;
+    rbn.calculate_particle(value(args, "temperature")?, &mut NullRecorder);
+    // flags override the policy the atom was saved with
+    let mut policy = rbn.get_site_policy().clone();
+    if args.value_of("size").is_some() {
+        policy.max_group_size = Some(value(args, "size")?);
+    }
+    if args.occurrences_of("strategy") > 0 || args.occurrences_of("order") > 0 {
+        let least_first = args.value_of("order") == Some("least");
+        policy.grouping = match args.value_of("strategy").unwrap() {
+            "out-degree" => Grouping::OutDegree { least_first },
+            "random-walk" => Grouping::RandomWalk {
+                seed: value(args, "seed")?,
+            },
+            "liveliness" => Grouping::Liveliness { least_first },
+            "partitions" => {
+                let raw = args.value_of("groups").unwrap();
+                let groups = raw
+                    .split(';')
+                    .map(|group| {
+                        group
+                            .split(',')
+                            .filter(|idx| !idx.trim().is_empty())
+                            .map(|idx| idx.trim().parse::<usize>())
+                            .collect::<Result<Vec<usize>, _>>()
+                    })
+                    .collect::<Result<Vec<Vec<usize>>, _>>()
+                    .map_err(|_| format!("invalid value {} for groups", raw))?;
+                Grouping::Partitions { groups }
+            }
+            _ => Grouping::Influence { least_first },
+        };
+    }
+    if args.value_of("min-size").is_some() {
+        policy.min_group_size = value(args, "min-size")?;
+    }
+    if let Some(small) = args.value_of("small") {
+        policy.small = if small == "discard" {
+            SmallGroups::Discard
+        } else {
+            SmallGroups::Merge
+        };
+    }
+    if args.is_present("overlap") {
+        policy.overlap = true;
+    }
+    if args.value_of("sites").is_some() {
+        policy.sites = Some(value(args, "sites")?);
+    }
+    policy
+        .check(rbn.get_node_count())
+        .map_err(|e| e.to_string())?;
+    let groups = rbn.generate_bonding_sites_with(&policy);
+    let sites: Vec<Site> = groups
+        .iter()
+        .map(|site| Site {
+            nodes: site
+                .get_interaction_list()
+                .iter()
+                .map(|nd| nd.borrow().get_id())
+                .collect(),
+            spike: rbn.get_bonding_prop(site),
+        })
+        .collect();
+    print_json(&sites)
+}
+
+fn react(args: &ArgMatches) -> Result<(), String> {
+    let mut reactor = match args.value_of("resume") {
+        Some(path) => {
+            let checkpoint: ReactorCheckpoint =
+                config::load(path).map_err(|e| format!("{}: {}", path, e))?;
+            Reactor::resume(checkpoint).map_err(|e| format!("{}: {}", path, e))?
+        }
+        None => {
+            let path = args.value_of("config").unwrap();
+            let cfg: ReactorConfig = config::load(path).map_err(|e| format!("{}: {}", path, e))?;
+            cfg.check().map_err(|e| format!("{}: {}", path, e))?;
+            Reactor::new(cfg)
+        }
+    };
+    let checkpoint = args.value_of("checkpoint");
+    let checkpoint_every: u64 = every(args)?;
+    let mut tables = stats_writer(args)?;
+    let sample_every = interval(args)?;
+    let mut sampler = Sampler::new(reactor.bonds_formed, reactor.bonds_broken);
+    if let Some(out) = tables.as_mut() {
+        if reactor.iteration == 0 {
+            out.write(&sampler.sample(0, &reactor.particles, 0, 0))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    let end = reactor.config.iterations;
+    let mut last = Some(reactor.iteration);
+    reactor
+        .run_checkpointed(1, |r| {
+            // the final call repeats the last iteration
+            if last == Some(r.iteration) {
+                return Ok(());
+            }
+            last = Some(r.iteration);
+            if let Some(out) = tables.as_mut() {
+                if due(r.iteration, end, sample_every) {
+                    out.write(&sampler.sample(
+                        r.iteration,
+                        &r.particles,
+                        r.bonds_formed,
+                        r.bonds_broken,
+                    ))?;
+                }
+            }
+            if let Some(path) = checkpoint {
+                if due(r.iteration, end, checkpoint_every) {
+                    config::save_json(&r.checkpoint(), path)?;
+                }
+            }
+            Ok(())
+        })
+        .map_err(|e| e.to_string())?;
+    if let Some(out) = tables {
+        out.into_inner().map_err(|e| e.to_string())?;
+    }
+    print_json(&reactor.summary())
+}
+
+fn lattice(args: &ArgMatches) -> Result<(), String> {
+    let mut lattice = match args.value_of("resume") {
+        Some(path) => {
+            let checkpoint: LatticeCheckpoint =
+                config::load(path).map_err(|e| format!("{}: {}", path, e))?;
+            Lattice::resume(checkpoint).map_err(|e| format!("{}: {}", path, e))?
+        }
+        None => {
+            let path = args.value_of("config").unwrap();
+            let cfg: LatticeConfig = config::load(path).map_err(|e| format!("{}: {}", path, e))?;
+            cfg.check().map_err(|e| format!("{}: {}", path, e))?;
+            Lattice::new(cfg)
+        }
     };
-    // println!("---------------------------------------");
-    // let mut newrbn2 = rbn::RBN::new(2, 12);
-    // newrbn2.calculate_particle(0, true);
-    // print!("{}", newrbn2);
-    // newrbn2.generate_interaction_groups_inf(23, false);
-    // println!("---------------------------------------");
-    // newrbn2.generate_interaction_groups_inf(23, true);
-
-    // atom.components.push(Rc::new(RefCell::new(newrbn2)));
-    // for _n in 1..20 {
-    //     atom.components
-    //         .push(Rc::new(RefCell::new(rbn::RBN::new(2, 12))));
-    // }
-
-    // for particle in atom.components {
-    //     let mut brwRBN: RefMut<_> = particle.borrow_mut();
-
-    //     println!("{}", brwRBN);
-    //     eprintln!("{:?}", brwRBN.calculate_particle(0b000000000101, false));
-    //     // println!("{}", brwRBN.fmt_cycle_liveliness());
-    //     //  o
-    //     // println!("{}", brwRBN.fmt_trans_liveliness());
-    // }
-    // newrbn.step();
-    // newrbn.sync();
-    // println!("{}", newrbn.fmt_state());
-    // newrbn.step();
-    // newrbn.sync();
-    // println!("{}", newrbn.fmt_state());
-    // newrbn.step();
-    // newrbn.sync();
-    // println!("{}", newrbn.fmt_state());
-    // newrbn.step();
-    // newrbn.sync();
-    // println!("{}", newrbn.fmt_state());
-
-    //let mut cl2 = 1;
-    //let mut cl1 = 1;
-
-    //while cl1 == cl2 {
-    //    eprintln!("Searching for that special something...");
-    //    cl1 = newrbn.calculate_cycle_ln(0b0101);
-    //    cl2 = newrbn.calculate_cycle_ln(0b1010);
-    //    newrbn = rbn::RBN::new(2, 12);
-    //}
-    //println!("{}, {}", cl1, cl2);
-    //println!("{:#?}", newrbn);
+    let checkpoint = args.value_of("checkpoint");
+    let checkpoint_every: u64 = every(args)?;
+    let snapshot_every: u64 = match args.value_of("snapshot-every") {
+        Some(_) => value(args, "snapshot-every")?,
+        None => 1000,
+    };
+    let mut snapshots = match args.value_of("snapshots") {
+        Some(path) => Some((
+            path,
+            BufWriter::new(File::create(path).map_err(|e| format!("{}: {}", path, e))?),
+        )),
+        None => None,
+    };
+    let mut tables = stats_writer(args)?;
+    let sample_every = interval(args)?;
+    let mut sampler = Sampler::new(lattice.bonds_formed, lattice.bonds_broken);
+    if let Some(out) = tables.as_mut() {
+        if lattice.iteration == 0 {
+            out.write(&sampler.sample(0, &lattice.particles, 0, 0))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    let end = lattice.config.reactor.iterations;
+    let mut last = Some(lattice.iteration);
+    lattice
+        .run_with(1, |l| {
+            // the final call repeats the last iteration
+            if last == Some(l.iteration) {
+                return Ok(());
+            }
+            last = Some(l.iteration);
+            if let Some((_path, out)) = snapshots.as_mut() {
+                if due(l.iteration, end, snapshot_every) {
+                    let line = serde_json::to_string(&l.snapshot())
+                        .map_err(|e| config::ConfigError::Parse(e.to_string()))?;
+                    writeln!(out, "{}", line)?;
+                }
+            }
+            if let Some(out) = tables.as_mut() {
+                if due(l.iteration, end, sample_every) {
+                    out.write(&sampler.sample(
+                        l.iteration,
+                        &l.particles,
+                        l.bonds_formed,
+                        l.bonds_broken,
+                    ))?;
+                }
+            }
+            if let Some(path) = checkpoint {
+                if due(l.iteration, end, checkpoint_every) {
+                    config::save_json(&l.checkpoint(), path)?;
+                }
+            }
+            Ok(())
+        })
+        .map_err(|e| e.to_string())?;
+    if let Some((path, mut out)) = snapshots {
+        out.flush().map_err(|e| format!("{}: {}", path, e))?;
+    }
+    if let Some(out) = tables {
+        out.into_inner().map_err(|e| e.to_string())?;
+    }
+    print_json(&lattice.summary())
+}
+
+fn catalysis(args: &ArgMatches) -> Result<(), String> {
+    let path = args.value_of("config").unwrap();
+    let cfg: ReactorConfig = config::load(path).map_err(|e| format!("{}: {}", path, e))?;
+    cfg.check().map_err(|e| format!("{}: {}", path, e))?;
+    let report = catalysis::screen(&cfg);
+    if let Some(out) = args.value_of("network") {
+        let network = ReactionNetwork::from_report(&report);
+        let text = if out.ends_with(".dot") {
+            network.to_dot()
+        } else {
+            serde_json::to_string_pretty(&network).map_err(|e| e.to_string())?
+        };
+        std::fs::write(out, text).map_err(|e| format!("{}: {}", out, e))?;
+    }
+    print_json(&report)
+}
+
+fn periodic(args: &ArgMatches) -> Result<(), String> {
+    let path = args.value_of("config").unwrap();
+    let cfg: PeriodicConfig = config::load(path).map_err(|e| format!("{}: {}", path, e))?;
+    cfg.check().map_err(|e| format!("{}: {}", path, e))?;
+    let table = periodic::tabulate(&cfg);
+    if let Some(out) = args.value_of("csv") {
+        let mut text = format!("{}\n", PeriodicTable::CSV_HEADER);
+        for row in table.csv_rows() {
+            text.push_str(&row);
+            text.push('\n');
+        }
+        std::fs::write(out, text).map_err(|e| format!("{}: {}", out, e))?;
+    }
+    print_json(&table)
+}
+
+fn experiment(args: &ArgMatches) -> Result<(), String> {
+    let path = args.value_of("spec").unwrap();
+    let spec: ExperimentSpec = config::load(path).map_err(|e| format!("{}: {}", path, e))?;
+    let result = match args.value_of("output") {
+        Some(out) => {
+            let file = File::create(out).map_err(|e| format!("{}: {}", out, e))?;
+            spec.run(BufWriter::new(file))
+        }
+        None => spec.run(io::stdout().lock()),
+    };
+    result.map_err(|e| format!("{}: {}", path, e))
+}
+
+fn ensemble(args: &ArgMatches) -> Result<(), String> {
+    let (cfg, resume) = match args.value_of("resume") {
+        Some(path) => {
+            let checkpoint: EnsembleCheckpoint =
+                config::load(path).map_err(|e| format!("{}: {}", path, e))?;
+            (checkpoint.config.clone(), Some(checkpoint))
+        }
+        None => {
+            let path = args.value_of("config").unwrap();
+            let cfg: EnsembleConfig = config::load(path).map_err(|e| format!("{}: {}", path, e))?;
+            (cfg, None)
+        }
+    };
+    let all = match args.value_of("checkpoint") {
+        Some(out) => cfg
+            .run_checkpointed(resume, every(args)?, |cp| config::save_json(cp, out))
+            .map_err(|e| format!("{}: {}", out, e))?,
+        None => cfg
+            .run_checkpointed(resume, 0, |_| Ok(()))
+            .map_err(|e| e.to_string())?,
+    };
+    for stats in all {
+        print_json(&stats)?;
+    }
+    Ok(())
+}
+
+fn evolve(args: &ArgMatches) -> Result<(), String> {
+    let path = args.value_of("config").unwrap();
+    let cfg: EvolveConfig = config::load(path).map_err(|e| format!("{}: {}", path, e))?;
+    cfg.check().map_err(|e| format!("{}: {}", path, e))?;
+    let fitness = cfg.fitness.clone();
+    let mut evolution = Evolution::new(cfg, fitness);
+    let mut result = Ok(());
+    evolution.run(|stats| {
+        if result.is_ok() {
+            result = print_json(stats);
+        }
+    });
+    result?;
+    let (atom, fitness) = evolution.best();
+    if let Some(out) = args.value_of("output") {
+        let rbn = atom.to_rbn().map_err(|e| e.to_string())?;
+        formats::write_atom(&rbn, out).map_err(|e| format!("{}: {}", out, e))?;
+    }
+    print_json(&Best {
+        fitness: *fitness,
+        atom,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_without_every() {
+        let dir = std::env::temp_dir();
+        let cfg = dir.join(format!("spiky-ensemble-{}.toml", process::id()));
+        let out = dir.join(format!("spiky-ensemble-{}.json", process::id()));
+        std::fs::write(&cfg, "n = 6\nk = 2\nnetworks = 3\nseed = 1\nsamples = 5\n").unwrap();
+        let matches = app().get_matches_from(vec![
+            "spiky",
+            "ensemble",
+            cfg.to_str().unwrap(),
+            "--checkpoint",
+            out.to_str().unwrap(),
+        ]);
+        let result = match matches.subcommand() {
+            ("ensemble", Some(sub)) => ensemble(sub),
+            _ => unreachable!(),
+        };
+        let saved: Result<EnsembleCheckpoint, _> = config::load(out.to_str().unwrap());
+        std::fs::remove_file(&cfg).unwrap();
+        std::fs::remove_file(&out).unwrap();
+        assert_eq!(result, Ok(()));
+        assert!(saved.unwrap().results[0].iter().all(|r| r.is_some()));
+    }
 }
@@ -1,7 +1,42 @@
-use std::cell::RefCell;
+use rand::Rng;
 use std::fmt;
 use std::fmt::Display;
-use std::rc::Rc;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Shared handle to a Node. It borrows like RefCell, try_read or try_write failing because a
+/// mutable borrow overlaps another borrow panics, but it is Send so whole RBNs can be moved
+/// between threads
+#[derive(Clone)]
+pub struct NodeRef(Arc<RwLock<Node>>);
+
+impl NodeRef {
+    pub fn new(node: Node) -> NodeRef {
+        NodeRef(Arc::new(RwLock::new(node)))
+    }
+
+    pub fn borrow(&self) -> RwLockReadGuard<'_, Node> {
+        self.0.try_read().expect("Node already mutably borrowed")
+    }
+
+    pub fn borrow_mut(&self) -> RwLockWriteGuard<'_, Node> {
+        self.0.try_write().expect("Node already borrowed")
+    }
+
+    /// True if both handles point at the same Node
+    pub fn ptr_eq(a: &NodeRef, b: &NodeRef) -> bool {
+        Arc::ptr_eq(&a.0, &b.0)
+    }
+}
+
+/// Only the id is shown, inputs usually loop back so printing the Node would never end
+impl fmt::Debug for NodeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.try_read() {
+            Ok(node) => write!(f, "NodeRef({})", node.get_id()),
+            Err(_) => write!(f, "NodeRef(<borrowed>)"),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Node {
@@ -12,13 +47,22 @@ pub struct Node {
     trans_liveliness: Option<i32>, // liveliness of the node
     cycle_liveliness: Option<i32>, // liveliness of the node
     id: u16,                       //id value , needs to be unique at rbn lvl not htis lvl
-    pub inputs: Vec<Rc<RefCell<Node>>>,
+    pub inputs: Vec<NodeRef>,
     influence: Option<u16>,
+    /// Candidate truth tables of a probabilistic Node and the probability of each being used in
+    /// a step, empty for an ordinary Node
+    functions: Vec<(Vec<bool>, f64)>,
 }
 
 impl Node {
     /// New Node with randome boolean table of size 2^<no_in>
     pub fn new(no_in: u8, node_id: u16) -> Node {
+        Node::new_with_rng(no_in, node_id, 0.5, &mut rand::thread_rng())
+    }
+
+    /// New Node with a boolean table of size 2^<no_in> drawn from <rng>, each entry is true with
+    /// probability <bias>
+    pub fn new_with_rng<R: Rng + ?Sized>(no_in: u8, node_id: u16, bias: f64, rng: &mut R) -> Node {
         let tbl_sz;
         match 1u8.checked_shl(no_in.into()) {
             //table has 2^no_in entries
@@ -34,7 +78,7 @@ impl Node {
         }
         let mut table = Vec::new();
         for _x in 0..tbl_sz {
-            table.push(rand::random::<bool>()); // generate bool function
+            table.push(rng.gen_bool(bias)); // generate bool function
         }
 
         let sz = table.len();
@@ -52,6 +96,7 @@ impl Node {
             id: node_id,
             inputs: vec![],
             influence: None,
+            functions: vec![],
         }
     }
 
@@ -71,6 +116,7 @@ impl Node {
             id: node_id,
             inputs: vec![],
             influence: None,
+            functions: vec![],
         }
     }
 
@@ -93,6 +139,11 @@ impl Node {
         return self.function_table[in_sum];
     }
 
+    /// Flips the calculated next state, noise applied between calc_next_state and update_state
+    pub fn flip_next_state(&mut self) {
+        self.s_nt = self.s_nt.map(|s| !s);
+    }
+
     /// Updates the current state with the new calculated resets the next state
     pub fn update_state(&mut self) {
         self.s_t = self.s_nt;
@@ -110,6 +161,65 @@ impl Node {
         self.s_t = Some(state);
         self.s_nt = None;
     }
+    /// Replaces the truth table, it must keep the size the inputs need
+    pub fn set_function_table(&mut self, tbl: Vec<bool>) {
+        if tbl.len() != self.tbl_size {
+            panic!(
+                "Truth table of size {} for Node {} which needs {}",
+                tbl.len(),
+                self.id,
+                self.tbl_size
+            );
+        }
+        self.function_table = tbl;
+    }
+    /// Makes this a probabilistic Node choosing between the (table, probability) pairs in <fns>
+    /// each step, or an ordinary one again if <fns> is empty. The first table is current until
+    /// the first choice
+    pub fn set_functions(&mut self, fns: Vec<(Vec<bool>, f64)>) {
+        if let Some((tbl, _p)) = fns.iter().find(|f| f.0.len() != self.tbl_size) {
+            panic!(
+                "Truth table of size {} for Node {} which needs {}",
+                tbl.len(),
+                self.id,
+                self.tbl_size
+            );
+        }
+        let total: f64 = fns.iter().map(|f| f.1).sum();
+        if !fns.is_empty() && ((total - 1.0).abs() > 1e-6 || fns.iter().any(|f| f.1 < 0.0)) {
+            panic!(
+                "Function probabilities of Node {} do not sum to 1 (sum = {})",
+                self.id, total
+            );
+        }
+        if let Some((tbl, _p)) = fns.first() {
+            self.function_table = tbl.clone();
+        }
+        self.functions = fns;
+    }
+    pub fn get_functions(&self) -> &Vec<(Vec<bool>, f64)> {
+        &self.functions
+    }
+    pub fn is_probabilistic(&self) -> bool {
+        !self.functions.is_empty()
+    }
+    /// Picks the truth table of a probabilistic Node for the next step, ordinary Nodes keep
+    /// theirs and draw nothing from <rng>
+    pub fn choose_function<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        if self.functions.is_empty() {
+            return;
+        }
+        let mut pick = rng.gen::<f64>();
+        for (tbl, p) in &self.functions {
+            if pick < *p {
+                self.function_table = tbl.clone();
+                return;
+            }
+            pick -= p;
+        }
+        // rounding left pick just above the last probability
+        self.function_table = self.functions.last().unwrap().0.clone();
+    }
     pub fn get_function_table(&self) -> &Vec<bool> {
         return &self.function_table;
     }
@@ -117,9 +227,11 @@ impl Node {
     pub fn get_id(&self) -> u16 {
         return self.id;
     }
+    /// Starts both liveliness counts from 0, a Node with no transient keeps a transient
+    /// liveliness of 0
     pub fn reset_liveliness(&mut self) {
-        self.trans_liveliness = None;
-        self.cycle_liveliness = None;
+        self.trans_liveliness = Some(0);
+        self.cycle_liveliness = Some(0);
     }
     /// Updates the liveliness counter based on current state
     pub fn update_trans_liveliness(&mut self) {
@@ -145,35 +257,42 @@ impl Node {
             self.cycle_liveliness = Some(self.cycle_liveliness.unwrap_or(0) - 1);
         }
     }
+    /// Sets both liveliness counters, for a Node whose dynamics were run as part of a larger
+    /// network
+    pub fn set_liveliness(&mut self, trans: i32, cycle: i32) {
+        self.trans_liveliness = Some(trans);
+        self.cycle_liveliness = Some(cycle);
+    }
     pub fn get_cycle_liveliness(&self) -> i32 {
         return self.cycle_liveliness.unwrap();
     }
     pub fn get_trans_liveliness(&self) -> i32 {
         return self.trans_liveliness.unwrap();
     }
+    /// Whether the Node has a cycle liveliness, from a calculation or set_liveliness
+    pub fn has_liveliness(&self) -> bool {
+        self.cycle_liveliness.is_some()
+    }
     pub fn get_influence(&self) -> Option<u16> {
         return self.influence;
     }
-    //TODO this function should actually sort inputs by influence and ID to give absolute ordering
-    //(which is the same as RBN::generate_interaction_groups_inf()) and then it can return
-    //first/last or even the hole list. Need this if you want to do k>2
-    pub fn get_input_by_inf(&self, least: bool) -> Option<Rc<RefCell<Node>>> {
-        if self.inputs.len() < 2 {
-            return None;
-        }
-
-        if self.inputs[0].borrow().get_influence() <= self.inputs[1].borrow().get_influence() {
-            if least {
-                return Some(self.inputs[0].clone());
-            } else {
-                return Some(self.inputs[1].clone());
-            }
+    /// Inputs sorted by influence then id, least influential first, the order
+    /// RBN::generate_interaction_groups_inf() walks them in
+    pub fn get_inputs_by_inf(&self) -> Vec<NodeRef> {
+        let mut inputs = self.inputs.clone();
+        inputs.sort_by_key(|input| {
+            let input = input.borrow();
+            (input.get_influence(), input.get_id())
+        });
+        inputs
+    }
+    /// Least or most influential input, None for a Node with no inputs
+    pub fn get_input_by_inf(&self, least: bool) -> Option<NodeRef> {
+        let mut inputs = self.get_inputs_by_inf();
+        if least {
+            inputs.into_iter().next()
         } else {
-            if least {
-                return Some(self.inputs[1].clone());
-            } else {
-                return Some(self.inputs[0].clone());
-            }
+            inputs.pop()
         }
     }
     //once the structure is set the influence is set, if it is still none then we set it to 0
@@ -184,7 +303,6 @@ impl Node {
         }
     }
     pub fn inc_influence(&mut self) {
-        println!("incrementing node influence");
         match self.influence {
             Some(x) => self.influence = Some(x + 1),
             None => self.influence = Some(1),
@@ -0,0 +1,159 @@
+//! Per step noise. Every step each Node's next state flips with probability eta, a thermal kick
+//! rather than the one off perturbations of perturb. Robustness measures how well an attractor
+//! holds on under the kicks and whether the liveliness bonding is built on survives them
+use crate::rbn::{RBNState, RBN};
+use crate::temp::{self, Temperature};
+use crate::trajectory::Trajectory;
+use crate::util::cycle_calc::IsSynchronous;
+use rand::Rng;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Robustness {
+    pub eta: f64,
+    pub steps: u64,
+    /// Length of the attractor reached from the temperature without noise
+    pub cycle: usize,
+    /// Fraction of the steps spent in a state of the original cycle
+    pub on_cycle: f64,
+    /// Times the noise knocked the RBN off the original cycle
+    pub escapes: u64,
+    /// Mean number of steps on the cycle before an escape, counted from reaching the cycle. None
+    /// if the RBN never escaped
+    pub mean_escape: Option<f64>,
+    /// Mean liveliness per step of each Node, -1 always off to 1 always on, over the cycle
+    /// without noise and over the noisy run
+    pub liveliness_clean: Vec<f64>,
+    pub liveliness_noisy: Vec<f64>,
+}
+
+impl Robustness {
+    /// True for each Node whose mean liveliness has the same sign with and without noise, the
+    /// sign is what decides which sites can bond
+    pub fn liveliness_kept(&self) -> Vec<bool> {
+        self.liveliness_clean
+            .iter()
+            .zip(&self.liveliness_noisy)
+            .map(|(clean, noisy)| sign(*clean) == sign(*noisy))
+            .collect()
+    }
+}
+
+fn sign(val: f64) -> i8 {
+    if val > 0.0 {
+        1
+    } else if val < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+fn mean_liveliness(on: &[u64], steps: u64) -> Vec<f64> {
+    on.iter()
+        .map(|c| {
+            if steps == 0 {
+                0.0
+            } else {
+                2.0 * *c as f64 / steps as f64 - 1.0
+            }
+        })
+        .collect()
+}
+
+/// Finds the attractor of <rbn> from <temperature>, then runs <steps> noisy steps with flip
+/// probability <eta> from its first state. The attractor is that of the current truth tables,
+/// probabilistic Nodes still choose theirs each noisy step. This overwrites the current state of
+/// <rbn>
+pub fn robustness<R: Rng + ?Sized>(
+    rbn: &RBN,
+    temperature: Temperature,
+    eta: f64,
+    steps: u64,
+    rng: &mut R,
+) -> Robustness {
+    let n = rbn.get_node_count();
+    let base = Trajectory::from_state(rbn, &temp::bits(temperature, n));
+    let cycle: HashSet<&Vec<bool>> = base.cycle_states().iter().collect();
+    let start = base.cycle_states()[0].clone();
+
+    let mut clean_on = vec![0u64; n];
+    for state in base.cycle_states() {
+        for (count, s) in clean_on.iter_mut().zip(state) {
+            if *s {
+                *count += 1;
+            }
+        }
+    }
+
+    rbn.set_state(&RBNState::from(start));
+    let mut noisy_on = vec![0u64; n];
+    let mut on_cycle = 0;
+    let mut escapes = 0;
+    let mut escape_steps = 0;
+    // steps on the cycle since it was last reached, None while off it
+    let mut sojourn = Some(0);
+    for _t in 0..steps {
+        rbn.step_with_noise(eta, rng);
+        rbn.sync();
+        let state = rbn.get_current_states();
+        for (count, s) in noisy_on.iter_mut().zip(&state) {
+            if *s {
+                *count += 1;
+            }
+        }
+        if cycle.contains(&state) {
+            on_cycle += 1;
+            sojourn = Some(sojourn.map_or(0, |s| s + 1));
+        } else if let Some(s) = sojourn.take() {
+            escapes += 1;
+            escape_steps += s + 1;
+        }
+    }
+
+    Robustness {
+        eta,
+        steps,
+        cycle: base.cycle,
+        on_cycle: if steps == 0 {
+            1.0
+        } else {
+            on_cycle as f64 / steps as f64
+        },
+        escapes,
+        mean_escape: if escapes == 0 {
+            None
+        } else {
+            Some(escape_steps as f64 / escapes as f64)
+        },
+        liveliness_clean: mean_liveliness(&clean_on, base.cycle as u64),
+        liveliness_noisy: mean_liveliness(&noisy_on, steps),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::rng::seeded;
+
+    #[test]
+    fn robustness_of_a_known_network() {
+        // n0 = !n1, n1 = n0 is a 4 cycle, n2 = n2 is stuck off from temperature 0
+        let tbls = vec![vec![true, false], vec![false, true], vec![false, true]];
+        let rbn = RBN::new_from_def_k(tbls, vec![vec![1], vec![0], vec![2]]);
+        let quiet = robustness(&rbn, 0, 0.0, 100, &mut seeded(1));
+        assert_eq!(quiet.cycle, 4);
+        assert_eq!(quiet.on_cycle, 1.0);
+        assert_eq!(quiet.escapes, 0);
+        assert_eq!(quiet.mean_escape, None);
+        assert_eq!(quiet.liveliness_clean, vec![0.0, 0.0, -1.0]);
+        assert_eq!(quiet.liveliness_noisy, quiet.liveliness_clean);
+
+        // n2 remembers a flip until the next one, so about half the time is spent off the cycle
+        let noisy = robustness(&rbn, 0, 0.01, 10000, &mut seeded(1));
+        assert!(noisy.on_cycle > 0.2 && noisy.on_cycle < 0.8);
+        assert!(noisy.escapes >= 1);
+        assert!(noisy.mean_escape.unwrap() >= 1.0);
+        assert_eq!(noisy, robustness(&rbn, 0, 0.01, 10000, &mut seeded(1)));
+    }
+}
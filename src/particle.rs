@@ -1,48 +1,271 @@
-use crate::rbn::RBNState;
-use crate::temp::Temperature;
-use crate::util::bonding::{BondingSite, IsBondable, IsSubSymbolic, Stability};
+use crate::formats::json::AtomDef;
+use crate::formats::FormatError;
+use crate::node::NodeRef;
+use crate::rbn::{RBNState, RBN};
+use crate::temp::{self, Temperature};
+use crate::util::bonding::{
+    bond_energy, spikes_bond, Bond, BondingSite, IsBondable, IsSubSymbolic, Stability,
+};
 use crate::util::cycle_calc::IsSynchronous;
 use crate::util::formatters::IsFormatable;
+use crate::util::grouping::SitePolicy;
+use crate::util::recorder::{NullRecorder, Phase, Recorder};
+use rand::prelude::IteratorRandom;
+use rand::thread_rng;
 use std::fmt;
 use std::fmt::Display;
+use std::ptr;
 
 ///Component is the generic trait for anything that can act in the subsymbolic system, both atomic
 ///structures and composite are components
-pub trait Component: IsBondable + IsSynchronous + IsSubSymbolic + IsFormatable + Display {}
+pub trait Component: IsBondable + IsSynchronous + IsSubSymbolic + IsFormatable + Display {
+    /// Definition the component can be rebuilt from, see ParticleDef::to_particle
+    fn to_def(&self) -> ComponentDef;
+
+    /// Every atom in the component in order, the atoms of each component in turn for a Particle
+    fn get_atoms(&self) -> Vec<&RBN>;
+
+    /// The two sites of every bond inside the component at any depth, those of its components
+    /// first. Empty for an atom
+    fn get_bonded_sites(&self) -> Vec<(&BondingSite, &BondingSite)>;
+
+    /// Shape of the component, see Structure
+    fn structure(&self) -> Structure;
+
+    /// The component as a Particle of its own, an atom is wrapped in one
+    fn into_particle(self: Box<Self>) -> Particle;
+
+    /// Name of the first graph node of bonding site <site> and of the cluster drawing it, in the
+    /// DOT body fmt_dot(<prefix>) gives. A particle's sites are drawn by the atom they belong to
+    fn fmt_dot_site(&self, prefix: &str, site: usize) -> (String, String);
+}
+
+/// Structure tree of a component, an atom is a leaf and a particle has its components as children
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Structure {
+    /// Nodes in the component, those of every atom for a particle
+    pub nodes: usize,
+    /// Bonding sites of the component, a particle has those of all its atoms
+    pub sites: usize,
+    /// Sites not part of a bond at any depth
+    pub free_sites: usize,
+    /// Bonds between the children, empty for an atom
+    pub bonds: Vec<Bond>,
+    pub components: Vec<Structure>,
+}
+
+impl Structure {
+    pub fn is_atom(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    /// Number of atoms under this component
+    pub fn atoms(&self) -> usize {
+        if self.is_atom() {
+            1
+        } else {
+            self.components.iter().map(|c| c.atoms()).sum()
+        }
+    }
+
+    /// Levels of nesting below this component, 0 for an atom
+    pub fn depth(&self) -> usize {
+        self.components
+            .iter()
+            .map(|c| c.depth() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let kind = if self.is_atom() { "atom" } else { "particle" };
+        writeln!(
+            f,
+            "{:indent$}{}: {} nodes, {} of {} sites free",
+            "",
+            kind,
+            self.nodes,
+            self.free_sites,
+            self.sites,
+            indent = indent
+        )?;
+        for comp in &self.components {
+            comp.fmt_indented(f, indent + 2)?;
+        }
+        for bond in &self.bonds {
+            writeln!(
+                f,
+                "{:indent$}bond {}.{} - {}.{}",
+                "",
+                bond.comp_a,
+                bond.site_a,
+                bond.comp_b,
+                bond.site_b,
+                indent = indent + 2
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// One line per component, children indented under their particle and followed by its bonds as
+/// component.site pairs
+impl fmt::Display for Structure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// Serialisable definition of a component, enough to rebuild its atoms and bonds exactly
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ComponentDef {
+    Atom(AtomDef),
+    Particle(ParticleDef),
+}
+
+/// Serialisable definition of a Particle, its components and the bonds between them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParticleDef {
+    pub components: Vec<ComponentDef>,
+    pub bonds: Vec<Bond>,
+}
+
+impl ParticleDef {
+    /// Rebuilds the Particle. Every atom is calculated alone from <init_state> and its bonding
+    /// sites generated again, so sites and bonding properties are those of the isolated atoms.
+    /// A particle saved after calculate_particle had the liveliness of its atoms in the whole
+    /// molecule, calling calculate_particle on the rebuilt particle runs the molecule again
+    pub fn to_particle(&self, init_state: Temperature) -> Result<Particle, FormatError> {
+        let mut comps: Vec<Box<dyn Component>> = Vec::with_capacity(self.components.len());
+        for def in &self.components {
+            match def {
+                ComponentDef::Atom(atom) => {
+                    let mut rbn = atom.to_rbn()?;
+                    if rbn.is_probabilistic() {
+                        return Err(FormatError::Invalid(
+                            "a probabilistic atom cannot be part of a particle".to_string(),
+                        ));
+                    }
+                    rbn.calculate_particle(init_state, &mut NullRecorder);
+                    comps.push(Box::new(rbn));
+                }
+                ComponentDef::Particle(particle) => {
+                    comps.push(Box::new(particle.to_particle(init_state)?))
+                }
+            }
+        }
+        let mut particle = Particle::new(comps);
+        for bond in &self.bonds {
+            for (comp, site) in &[(bond.comp_a, bond.site_a), (bond.comp_b, bond.site_b)] {
+                if *comp >= particle.components.len()
+                    || *site >= particle.components[*comp].get_all_bonding_sites().len()
+                {
+                    return Err(FormatError::Invalid(format!(
+                        "bond to missing site {} of component {}",
+                        site, comp
+                    )));
+                }
+            }
+            particle.bonds.push(*bond);
+        }
+        Ok(particle)
+    }
+}
 
 pub struct Particle {
     pub components: Vec<Box<dyn Component>>,
+    /// Bonding sites of every component in order, bonded or not. Site <i> of component <c> is at
+    /// the sum of the site counts of the components before <c> plus <i>
     pub bonding_sites: Vec<BondingSite>,
+    pub bonds: Vec<Bond>,
 }
 
 impl Particle {
+    /// Particle of <comp> with no bonds, every component generates its bonding sites
     pub fn new(comp: Vec<Box<dyn Component>>) -> Particle {
-        //TODO: go through comp and full out all bonding_sites
-        // for mut component in comp {
-        //     component.generate_bonding_sites();
-        // }
-
-        Particle {
+        let mut particle = Particle {
             components: comp,
             bonding_sites: Vec::new(),
-        }
-        .gen_bonds()
+            bonds: Vec::new(),
+        };
+        particle.generate_bonding_sites();
+        particle
+    }
+
+    /// Collects the bonding sites of the components, needed whenever the components change
+    fn aggregate_sites(&mut self) {
+        self.bonding_sites = self
+            .components
+            .iter()
+            .flat_map(|c| c.get_all_bonding_sites().into_iter().cloned())
+            .collect();
     }
 
-    fn gen_bonds(mut self) -> Self {
-        for idx in 0..self.components.len() {
-            self.components[idx].generate_bonding_sites();
+    /// (component, site) of site <site> of the particle, None if there is no such site
+    pub fn locate_site(&self, site: usize) -> Option<(usize, usize)> {
+        let mut offset = 0;
+        for (comp_idx, comp) in self.components.iter().enumerate() {
+            let count = comp.get_all_bonding_sites().len();
+            if site < offset + count {
+                return Some((comp_idx, site - offset));
+            }
+            offset += count;
         }
-        self
+        None
     }
 }
 
-impl Component for Particle {}
+impl Component for Particle {
+    fn to_def(&self) -> ComponentDef {
+        ComponentDef::Particle(self.to_particle_def())
+    }
+
+    fn get_atoms(&self) -> Vec<&RBN> {
+        self.components.iter().flat_map(|c| c.get_atoms()).collect()
+    }
 
+    fn get_bonded_sites(&self) -> Vec<(&BondingSite, &BondingSite)> {
+        let mut sites: Vec<(&BondingSite, &BondingSite)> = self
+            .components
+            .iter()
+            .flat_map(|c| c.get_bonded_sites())
+            .collect();
+        for bond in &self.bonds {
+            sites.push((
+                self.components[bond.comp_a].get_all_bonding_sites()[bond.site_a],
+                self.components[bond.comp_b].get_all_bonding_sites()[bond.site_b],
+            ));
+        }
+        sites
+    }
+
+    fn structure(&self) -> Structure {
+        let components: Vec<Structure> = self.components.iter().map(|c| c.structure()).collect();
+        Structure {
+            nodes: components.iter().map(|c| c.nodes).sum(),
+            sites: self.bonding_sites.len(),
+            free_sites: self.get_free_bonding_sites().map_or(0, |f| f.len()),
+            bonds: self.bonds.clone(),
+            components,
+        }
+    }
+
+    fn into_particle(self: Box<Self>) -> Particle {
+        *self
+    }
+
+    fn fmt_dot_site(&self, prefix: &str, site: usize) -> (String, String) {
+        let (comp, comp_site) = self
+            .locate_site(site)
+            .expect("bonds are between existing sites");
+        self.components[comp].fmt_dot_site(&format!("{}c{}_", prefix, comp), comp_site)
+    }
+}
+
+/// The structure tree, see Structure
 impl fmt::Display for Particle {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut form_string = String::new();
-        write!(f, "{}", form_string)
+        write!(f, "{}", self.structure())
     }
 }
 
@@ -65,11 +288,304 @@ impl IsFormatable for Particle {
     fn fmt_trans_liveliness(&self) -> String {
         return String::new();
     }
+
+    /// Each component is drawn in its own cluster, bonds are undirected edges between the
+    /// clusters of the two bonded sites
+    fn fmt_dot(&self, prefix: &str) -> String {
+        let mut form_string = String::new();
+        for (idx, comp) in self.components.iter().enumerate() {
+            form_string.push_str(&format!(
+                "subgraph cluster_{}c{} {{\nlabel=\"component {}\";\n",
+                prefix, idx, idx
+            ));
+            form_string.push_str(&comp.fmt_dot(&format!("{}c{}_", prefix, idx)));
+            form_string.push_str("}\n");
+        }
+        for bond in &self.bonds {
+            let end_a = self.components[bond.comp_a]
+                .fmt_dot_site(&format!("{}c{}_", prefix, bond.comp_a), bond.site_a);
+            let end_b = self.components[bond.comp_b]
+                .fmt_dot_site(&format!("{}c{}_", prefix, bond.comp_b), bond.site_b);
+            form_string.push_str(&format!(
+                "{} -> {} [ltail={}, lhead={}, dir=none, style=bold, color=red];\n",
+                end_a.0, end_b.0, end_a.1, end_b.1
+            ));
+        }
+        form_string
+    }
+}
+
+impl Particle {
+    pub fn to_particle_def(&self) -> ParticleDef {
+        ParticleDef {
+            components: self.components.iter().map(|c| c.to_def()).collect(),
+            bonds: self.bonds.clone(),
+        }
+    }
+
+    /// (component, site) of every bonding site on a component that is not part of a bond, either
+    /// between the components or inside a component that is itself a particle
+    pub fn get_free_sites(&self) -> Vec<(usize, usize)> {
+        let mut free = Vec::new();
+        for (comp_idx, comp) in self.components.iter().enumerate() {
+            let inner = comp.get_free_bonding_sites().unwrap_or_default();
+            for (site_idx, site) in comp.get_all_bonding_sites().into_iter().enumerate() {
+                let bonded = !inner.iter().any(|f| ptr::eq(*f, site))
+                    || self.bonds.iter().any(|b| {
+                        (b.comp_a, b.site_a) == (comp_idx, site_idx)
+                            || (b.comp_b, b.site_b) == (comp_idx, site_idx)
+                    });
+                if !bonded {
+                    free.push((comp_idx, site_idx));
+                }
+            }
+        }
+        free
+    }
+
+    /// Energy of bond <idx> between the components, see bond_energy. A site without a bonding
+    /// property counts as a spike of 0
+    pub fn get_bond_energy(&self, idx: usize) -> i64 {
+        let bond = &self.bonds[idx];
+        bond_energy(
+            self.get_site_prop(bond.comp_a, bond.site_a).unwrap_or(0),
+            self.get_site_prop(bond.comp_b, bond.site_b).unwrap_or(0),
+        )
+    }
+
+    /// Indexes of the bonds between the components whose sites' spikes can no longer bond, see
+    /// spikes_bond. A site without a bonding property never bonds
+    pub fn failing_bonds(&self) -> Vec<usize> {
+        (0..self.bonds.len())
+            .filter(|idx| {
+                let bond = &self.bonds[*idx];
+                match (
+                    self.get_site_prop(bond.comp_a, bond.site_a),
+                    self.get_site_prop(bond.comp_b, bond.site_b),
+                ) {
+                    (Some(a), Some(b)) => !spikes_bond(a, b),
+                    _ => true,
+                }
+            })
+            .collect()
+    }
+
+    /// Total energy of the bonds at every depth, 0 for an atom
+    pub fn energy(&self) -> i64 {
+        self.get_bonded_sites()
+            .iter()
+            .map(|(a, b)| {
+                bond_energy(
+                    self.get_bonding_prop(a).unwrap_or(0),
+                    self.get_bonding_prop(b).unwrap_or(0),
+                )
+            })
+            .sum()
+    }
+
+    /// Bonding property of site <site> of component <comp>
+    pub fn get_site_prop(&self, comp: usize, site: usize) -> Option<i32> {
+        let comp = &self.components[comp];
+        comp.get_bonding_prop(comp.get_all_bonding_sites()[site])
+    }
+
+    /// Nests this particle and <other> as the two components of a new particle, bonded between
+    /// their sites <site> and <other_site> (indexes into get_all_bonding_sites, which should be
+    /// free). Unlike bond_with the two stay whole and can be taken apart again with decompose
+    pub fn compose(self, site: usize, other: Particle, other_site: usize) -> Particle {
+        let mut particle = Particle {
+            components: vec![Box::new(self), Box::new(other)],
+            bonding_sites: vec![],
+            bonds: vec![Bond {
+                comp_a: 0,
+                site_a: site,
+                comp_b: 1,
+                site_b: other_site,
+            }],
+        };
+        particle.aggregate_sites();
+        particle
+    }
+
+    /// Breaks every bond between the components and returns each as a particle of its own,
+    /// components that are particles keep their bonds. Taking a particle apart one level at a time
+    /// follows the order it was composed in
+    pub fn decompose(self) -> Vec<Particle> {
+        self.components
+            .into_iter()
+            .map(|c| c.into_particle())
+            .collect()
+    }
+
+    /// Joins <other> onto this particle with a bond between <site> of this particle and
+    /// <other_site> of <other>, both (component, site). The components of <other> are appended
+    /// after this particle's
+    pub fn bond_with(
+        mut self,
+        site: (usize, usize),
+        other: Particle,
+        other_site: (usize, usize),
+    ) -> Particle {
+        let offset = self.components.len();
+        self.components.extend(other.components);
+        for bond in other.bonds {
+            self.bonds.push(Bond {
+                comp_a: bond.comp_a + offset,
+                comp_b: bond.comp_b + offset,
+                ..bond
+            });
+        }
+        self.bonds.push(Bond {
+            comp_a: site.0,
+            site_a: site.1,
+            comp_b: other_site.0 + offset,
+            site_b: other_site.1,
+        });
+        self.aggregate_sites();
+        self
+    }
+
+    /// Removes bond <idx> and splits the particle into the pieces that are still connected. The
+    /// pieces keep their components in the original order
+    pub fn break_bond(mut self, idx: usize) -> Vec<Particle> {
+        self.bonds.remove(idx);
+        // label every component with the lowest component it is connected to
+        let mut group: Vec<usize> = (0..self.components.len()).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for bond in &self.bonds {
+                let low = group[bond.comp_a].min(group[bond.comp_b]);
+                for end in &[bond.comp_a, bond.comp_b] {
+                    if group[*end] != low {
+                        group[*end] = low;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        let mut pieces: Vec<Particle> = Vec::new();
+        let mut piece_of = vec![0; self.components.len()];
+        let mut new_idx = vec![0; self.components.len()];
+        for (comp_idx, comp) in self.components.into_iter().enumerate() {
+            let root = group[comp_idx];
+            let piece = if root == comp_idx {
+                pieces.push(Particle {
+                    components: vec![],
+                    bonding_sites: vec![],
+                    bonds: vec![],
+                });
+                pieces.len() - 1
+            } else {
+                piece_of[root]
+            };
+            piece_of[comp_idx] = piece;
+            new_idx[comp_idx] = pieces[piece].components.len();
+            pieces[piece].components.push(comp);
+        }
+        for bond in self.bonds {
+            pieces[piece_of[bond.comp_a]].bonds.push(Bond {
+                comp_a: new_idx[bond.comp_a],
+                comp_b: new_idx[bond.comp_b],
+                ..bond
+            });
+        }
+        for piece in &mut pieces {
+            piece.aggregate_sites();
+        }
+        pieces
+    }
+
+    /// The particle as one RBN, the Nodes of every atom in get_atoms order joined by the bonds at
+    /// every depth, see get_bonded_sites. Each bond cross-links its two sites: the i-th Nodes of
+    /// both sites are paired up, as many pairs as the smaller site has Nodes, and each Node of a
+    /// pair takes its first input from the other instead. Bonds are applied in order, so a Node
+    /// in several bonds keeps the last cross-link
+    pub fn composite(&self) -> RBN {
+        let mut nodes: Vec<NodeRef> = Vec::new();
+        let mut tables = Vec::new();
+        let mut inputs = Vec::new();
+        let mut functions = Vec::new();
+        for atom in self.get_atoms() {
+            let offset = nodes.len();
+            for idx in 0..atom.get_node_count() {
+                nodes.push(atom.get_node(idx));
+                tables.push(atom.get_function_table(idx));
+                inputs.push(
+                    atom.get_node_inputs(idx)
+                        .iter()
+                        .map(|i| i + offset)
+                        .collect::<Vec<usize>>(),
+                );
+                functions.push(atom.get_node_functions(idx));
+            }
+        }
+        let flat = |site: &BondingSite| -> Vec<usize> {
+            site.get_interaction_list()
+                .iter()
+                .map(|nd| {
+                    nodes
+                        .iter()
+                        .position(|own| NodeRef::ptr_eq(own, nd))
+                        .expect("bonding sites are made of the particle's own Nodes")
+                })
+                .collect()
+        };
+        for (site_a, site_b) in self.get_bonded_sites() {
+            let site_a = flat(site_a);
+            let site_b = flat(site_b);
+            for (a, b) in site_a.iter().zip(&site_b) {
+                if let Some(first) = inputs[*a].first_mut() {
+                    *first = *b;
+                }
+                if let Some(first) = inputs[*b].first_mut() {
+                    *first = *a;
+                }
+            }
+        }
+        let composite = RBN::new_from_def_k(tables, inputs);
+        for (idx, fns) in functions.into_iter().enumerate() {
+            composite.set_node_functions(idx, fns);
+        }
+        composite
+    }
 }
 
 impl IsSubSymbolic for Particle {
-    fn calculate_particle(&mut self, init_state: Temperature, verbose: bool) -> Stability {
-        return Stability::Stable;
+    /// Runs the composite RBN, every atom starting from <init_state> as it would on its own, and
+    /// gives each atom's Nodes their liveliness in the whole molecule. The particle is Stable if
+    /// every bond, at any depth, still joins sites whose spikes can bond. <rec> sees the
+    /// composite's states
+    fn calculate_particle(&mut self, init_state: Temperature, rec: &mut dyn Recorder) -> Stability {
+        assert!(
+            !self.get_atoms().iter().any(|atom| atom.is_probabilistic()),
+            "a particle with a probabilistic atom has no single cycle to calculate"
+        );
+        let mut composite = self.composite();
+        let mut start = Vec::with_capacity(composite.get_node_count());
+        for atom in self.get_atoms() {
+            start.extend(temp::bits(init_state, atom.get_node_count()));
+        }
+        let (cycle, transient) = composite.calculate_from(&RBNState::from(start), rec);
+        let trans = composite.get_liveliness(Phase::Transient);
+        let cyc = composite.get_liveliness(Phase::Cycle);
+        let mut offset = 0;
+        for atom in self.get_atoms() {
+            let n = atom.get_node_count();
+            atom.set_liveliness(&trans[offset..offset + n], &cyc[offset..offset + n]);
+            offset += n;
+        }
+        let stable = self.get_bonded_sites().iter().all(|(site_a, site_b)| {
+            match (self.get_bonding_prop(site_a), self.get_bonding_prop(site_b)) {
+                (Some(a), Some(b)) => spikes_bond(a, b),
+                _ => false,
+            }
+        });
+        if stable {
+            Stability::Stable { cycle, transient }
+        } else {
+            Stability::Unstable { cycle, transient }
+        }
     }
 }
 
@@ -83,31 +599,166 @@ impl IsSynchronous for Particle {
 }
 
 impl IsBondable for Particle {
-    /// Generates the bonding sites based on the underlying representatin
+    /// Every component generates its bonding sites again and the particle collects them. The
+    /// sites must come out the same for the bonds to still make sense
     fn generate_bonding_sites(&mut self) -> Vec<BondingSite> {
-        return vec![];
+        for comp in &mut self.components {
+            comp.generate_bonding_sites();
+        }
+        self.aggregate_sites();
+        self.bonding_sites.clone()
     }
 
-    /// Returns Bonding Property for a specific &BondingSite
-    /// If the BondingSite is not present on the particle returns None
+    /// As generate_bonding_sites with every atom following <policy>
+    fn generate_bonding_sites_with(&mut self, policy: &SitePolicy) -> Vec<BondingSite> {
+        for comp in &mut self.components {
+            comp.generate_bonding_sites_with(policy);
+        }
+        self.aggregate_sites();
+        self.bonding_sites.clone()
+    }
+
+    /// Bonding Property from the atom <bs> belongs to, None if it is not on the particle
     fn get_bonding_prop(&self, bs: &BondingSite) -> Option<i32> {
-        return None;
+        self.components.iter().find_map(|c| c.get_bonding_prop(bs))
     }
 
     /// Returns pointers to all BondingSites on the Particle
     fn get_all_bonding_sites(&self) -> Vec<&BondingSite> {
-        return vec![];
+        self.bonding_sites.iter().collect()
     }
 
-    /// Returns pointers to all BondingSites not currently part of a bond on the Particle
+    /// Returns pointers to all BondingSites not part of a bond at any depth
     /// If there are no free sites returns None
     fn get_free_bonding_sites(&self) -> Option<Vec<&BondingSite>> {
-        return None;
+        let mut offsets = Vec::with_capacity(self.components.len());
+        let mut offset = 0;
+        for comp in &self.components {
+            offsets.push(offset);
+            offset += comp.get_all_bonding_sites().len();
+        }
+        let free: Vec<&BondingSite> = self
+            .get_free_sites()
+            .into_iter()
+            .map(|(comp, site)| &self.bonding_sites[offsets[comp] + site])
+            .collect();
+        if free.is_empty() {
+            None
+        } else {
+            Some(free)
+        }
     }
 
     /// Returns a random bonding site not currently part of a bond
     /// If there are no free sites returns None
     fn get_rand_free_bonding_site(&self) -> Option<&BondingSite> {
-        return None;
+        self.get_free_bonding_sites()?
+            .into_iter()
+            .choose(&mut thread_rng())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::grouping::{Grouping, SitePolicy};
+
+    /// Atom of two Nodes, Node 0 applies <tbl> to itself and Node 1 copies Node 0, with a site for
+    /// each Node
+    fn atom(tbl: Vec<bool>) -> Box<dyn Component> {
+        let mut rbn = RBN::new_from_def_k(vec![tbl, vec![false, true]], vec![vec![0], vec![0]]);
+        rbn.set_site_policy(SitePolicy {
+            grouping: Grouping::Partitions {
+                groups: vec![vec![0], vec![1]],
+            },
+            ..SitePolicy::default()
+        });
+        rbn.calculate_particle(0, &mut NullRecorder);
+        Box::new(rbn)
+    }
+
+    #[test]
+    fn composite_dynamics() {
+        // a copy of itself stuck off and a negation of itself flipping every step
+        let copy = Particle::new(vec![atom(vec![false, true])]);
+        let not = Particle::new(vec![atom(vec![true, false])]);
+        assert_eq!(copy.get_site_prop(0, 0), Some(-1));
+        assert_eq!(not.get_site_prop(0, 0), Some(0));
+
+        let mut alone = Particle::new(vec![atom(vec![false, true])]);
+        assert!(alone.calculate_particle(0, &mut NullRecorder).is_stable());
+
+        let mut both = copy.bond_with((0, 0), not, (0, 0));
+        let composite = both.composite();
+        assert_eq!(composite.get_node_count(), 4);
+        // the two bonded Nodes now read each other, the copies are unchanged
+        assert_eq!(composite.get_node_inputs(0), &vec![2]);
+        assert_eq!(composite.get_node_inputs(1), &vec![0]);
+        assert_eq!(composite.get_node_inputs(2), &vec![0]);
+        assert_eq!(composite.get_node_inputs(3), &vec![2]);
+
+        // Node 0 follows Node 2 which negates Node 0, a 4 cycle reached after one step
+        let stability = both.calculate_particle(0, &mut NullRecorder);
+        assert_eq!(
+            stability,
+            Stability::Unstable {
+                cycle: 4,
+                transient: 1
+            }
+        );
+        // every Node is on for half the cycle, so neither bonded site has a spike any more
+        for atom in both.get_atoms() {
+            assert_eq!(atom.get_liveliness(Phase::Cycle), vec![0, 0]);
+        }
+        assert_eq!(both.get_site_prop(0, 0), Some(0));
+    }
+
+    #[test]
+    fn hierarchical_particles() {
+        let pair = |a: Vec<bool>, b: Vec<bool>| {
+            Particle::new(vec![atom(a)]).bond_with((0, 0), Particle::new(vec![atom(b)]), (0, 0))
+        };
+        let left = pair(vec![false, true], vec![true, false]);
+        let right = pair(vec![true, false], vec![false, true]);
+        // sites of both atoms are collected, the bonded ones are not free
+        assert_eq!(left.get_all_bonding_sites().len(), 4);
+        assert_eq!(left.get_free_sites(), vec![(0, 1), (1, 1)]);
+        assert_eq!(left.get_free_bonding_sites().unwrap().len(), 2);
+        assert_eq!(left.locate_site(3), Some((1, 1)));
+        assert_eq!(left.locate_site(4), None);
+
+        // site 1 of each pair is the second site of its first atom
+        let mut nested = left.compose(1, right, 3);
+        assert_eq!(nested.components.len(), 2);
+        assert_eq!(nested.get_free_sites(), vec![(0, 3), (1, 1)]);
+        assert_eq!(nested.get_bonded_sites().len(), 3);
+        let tree = nested.structure();
+        assert_eq!((tree.nodes, tree.sites, tree.free_sites), (8, 8, 2));
+        assert_eq!((tree.atoms(), tree.depth()), (4, 2));
+        assert_eq!(
+            nested.to_string().lines().next(),
+            Some("particle: 8 nodes, 2 of 8 sites free")
+        );
+        assert_eq!(nested.to_string().lines().count(), 10);
+
+        // the composite has every bond at every depth
+        let composite = nested.composite();
+        assert_eq!(composite.get_node_inputs(0), &vec![2]);
+        assert_eq!(composite.get_node_inputs(1), &vec![7]);
+        assert_eq!(composite.get_node_inputs(7), &vec![1]);
+        nested.calculate_particle(0, &mut NullRecorder);
+
+        // the definition keeps the nesting
+        let rebuilt = nested.to_particle_def().to_particle(0).unwrap();
+        assert_eq!(rebuilt.structure(), nested.structure());
+
+        let parts = nested.decompose();
+        assert_eq!(parts.len(), 2);
+        assert!(parts
+            .iter()
+            .all(|p| p.components.len() == 2 && p.bonds.len() == 1));
+        let atoms: Vec<Particle> = parts.into_iter().flat_map(|p| p.decompose()).collect();
+        assert_eq!(atoms.len(), 4);
+        assert!(atoms.iter().all(|a| a.structure().depth() == 1));
     }
 }
@@ -0,0 +1,136 @@
+//! Probabilistic Boolean networks. Every step each probabilistic Node chooses one of its candidate
+//! truth tables at random, so there is no single attractor to find; instead the long run
+//! behaviour is the stationary distribution over states, estimated here by Monte Carlo
+use crate::rbn::{RBNState, RBN};
+use crate::util::cycle_calc::IsSynchronous;
+use rand::Rng;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stationary {
+    /// Steps the distribution was estimated from, after the burn in
+    pub steps: u64,
+    /// Fraction of the steps each Node was on
+    pub node_on: Vec<f64>,
+    /// Times each state was visited
+    #[serde(skip)]
+    pub visits: HashMap<Vec<bool>, u64>,
+}
+
+impl Stationary {
+    /// Estimated probability of <state>
+    pub fn probability(&self, state: &[bool]) -> f64 {
+        if self.steps == 0 {
+            return 0.0;
+        }
+        self.visits.get(state).cloned().unwrap_or(0) as f64 / self.steps as f64
+    }
+
+    /// The <count> most visited states and their probabilities, most likely first. Ties are
+    /// broken by state so the order is reproducible
+    pub fn top(&self, count: usize) -> Vec<(Vec<bool>, f64)> {
+        let mut states: Vec<(&Vec<bool>, &u64)> = self.visits.iter().collect();
+        states.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        states
+            .into_iter()
+            .take(count)
+            .map(|(state, _visits)| (state.clone(), self.probability(state)))
+            .collect()
+    }
+}
+
+/// Runs <rbn> from <init>, Node i taking <init[i]>, for <burn_in> steps and then counts the states
+/// of the next <steps> steps. Truth tables are chosen from <rng>, ordinary RBNs simply count their
+/// attractor. This overwrites the current state of <rbn>
+pub fn estimate<R: Rng + ?Sized>(
+    rbn: &RBN,
+    init: &[bool],
+    burn_in: u64,
+    steps: u64,
+    rng: &mut R,
+) -> Stationary {
+    rbn.set_state(&RBNState::from(init.to_vec()));
+    for _t in 0..burn_in {
+        rbn.step_with_rng(rng);
+        rbn.sync();
+    }
+    let mut on = vec![0u64; rbn.get_node_count()];
+    let mut visits = HashMap::new();
+    for _t in 0..steps {
+        rbn.step_with_rng(rng);
+        rbn.sync();
+        let state = rbn.get_current_states();
+        for (count, s) in on.iter_mut().zip(&state) {
+            if *s {
+                *count += 1;
+            }
+        }
+        *visits.entry(state).or_insert(0) += 1;
+    }
+    Stationary {
+        steps,
+        node_on: on
+            .iter()
+            .map(|c| {
+                if steps == 0 {
+                    0.0
+                } else {
+                    *c as f64 / steps as f64
+                }
+            })
+            .collect(),
+        visits,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::json::AtomDef;
+    use crate::particle::{ComponentDef, ParticleDef};
+    use crate::util::bonding::IsSubSymbolic;
+    use crate::util::recorder::NullRecorder;
+    use crate::util::rng::seeded;
+
+    /// n0 copies or negates itself with equal chance so is on half the time, n1 = n0
+    fn coin() -> RBN {
+        let rbn = RBN::new_from_def_k(
+            vec![vec![false, true], vec![false, true]],
+            vec![vec![0], vec![0]],
+        );
+        rbn.set_node_functions(0, vec![(vec![false, true], 0.5), (vec![true, false], 0.5)]);
+        rbn
+    }
+
+    #[test]
+    fn stationary_of_a_known_network() {
+        let rbn = coin();
+        assert!(rbn.is_probabilistic());
+        let dist = estimate(&rbn, &[false, false], 100, 20000, &mut seeded(3));
+        assert_eq!(dist.steps, 20000);
+        assert!((dist.node_on[0] - 0.5).abs() < 0.02);
+        assert!((dist.node_on[1] - 0.5).abs() < 0.02);
+        for state in &[[false, false], [true, true], [true, false], [false, true]] {
+            assert!((dist.probability(state) - 0.25).abs() < 0.02);
+        }
+        assert_eq!(dist.top(10).len(), 4);
+        let again = estimate(&rbn, &[false, false], 100, 20000, &mut seeded(3));
+        assert_eq!(again, dist);
+    }
+
+    #[test]
+    #[should_panic(expected = "a probabilistic RBN has no single cycle to calculate")]
+    fn no_cycle_to_calculate() {
+        coin().calculate_particle(0, &mut NullRecorder);
+    }
+
+    #[test]
+    fn not_a_particle() {
+        let def = ParticleDef {
+            components: vec![ComponentDef::Atom(AtomDef::from_rbn(&coin()))],
+            bonds: vec![],
+        };
+        let err = def.to_particle(0).err().unwrap();
+        assert!(err.to_string().contains("probabilistic"));
+    }
+}
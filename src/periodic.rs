@@ -0,0 +1,294 @@
+//! Periodic table of spiky-RBN atoms. Random atoms are classed by their spike signature, how many
+//! of their bonding sites have a positive, negative or zero spike, and the classes are tabulated
+//! against each other: how many site pairs could bond, and how often such a bond holds once the
+//! two atoms' dynamics are coupled (see Particle::composite)
+use crate::config::{check_atom_params, ConfigError};
+use crate::formats::json::AtomDef;
+use crate::particle::{Component, Particle};
+use crate::rbn::RBN;
+use crate::temp::Temperature;
+use crate::util::bonding::{spikes_bond, IsSubSymbolic};
+use crate::util::grouping::SitePolicy;
+use crate::util::recorder::NullRecorder;
+use crate::util::rng::seeded;
+use rand::seq::SliceRandom;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A class's signature, its atoms and their cycle lengths
+type Members = (Signature, Vec<AtomDef>, Vec<u64>);
+
+fn default_bias() -> f64 {
+    0.5
+}
+
+fn default_trials() -> usize {
+    20
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PeriodicConfig {
+    /// Atoms generated
+    pub atoms: usize,
+    /// Nodes per atom
+    pub n: u16,
+    /// Inputs per Node
+    pub k: u8,
+    /// Probability of each truth table entry being true
+    #[serde(default = "default_bias")]
+    pub bias: f64,
+    /// Initial state every atom is calculated from
+    #[serde(default)]
+    pub temperature: Temperature,
+    /// Atom i is generated from seed <seed> + i, bond trials from <seed> + <atoms>
+    pub seed: u64,
+    /// How each atom's bonding sites are generated
+    #[serde(default)]
+    pub sites: SitePolicy,
+    /// Bonds tried between each pair of classes that can bond
+    #[serde(default = "default_trials")]
+    pub trials: usize,
+}
+
+impl PeriodicConfig {
+    /// Checks the configuration describes atoms that can be generated
+    pub fn check(&self) -> Result<(), ConfigError> {
+        check_atom_params(self.n, self.k, self.bias)?;
+        self.sites.check(self.n as usize)
+    }
+}
+
+/// Number of bonding sites with each sign of spike, the class of an atom
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct Signature {
+    pub positive: usize,
+    pub negative: usize,
+    pub zero: usize,
+}
+
+impl Signature {
+    pub fn of(spikes: &[i32]) -> Signature {
+        Signature {
+            positive: spikes.iter().filter(|s| **s > 0).count(),
+            negative: spikes.iter().filter(|s| **s < 0).count(),
+            zero: spikes.iter().filter(|s| **s == 0).count(),
+        }
+    }
+
+    pub fn sites(&self) -> usize {
+        self.positive + self.negative + self.zero
+    }
+
+    /// Fraction of the site pairs of an atom of this class and one of <other> whose spikes can
+    /// bond
+    pub fn reactivity(&self, other: &Signature) -> f64 {
+        let pairs = self.sites() * other.sites();
+        if pairs == 0 {
+            return 0.0;
+        }
+        (self.positive * other.negative + self.negative * other.positive) as f64 / pairs as f64
+    }
+}
+
+/// Written as +p-noz, e.g. +2-1o0 for two positive sites, one negative and none at zero
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "+{}-{}o{}", self.positive, self.negative, self.zero)
+    }
+}
+
+/// A row of the table
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Class {
+    pub name: String,
+    pub signature: Signature,
+    /// Atoms in the class
+    pub count: usize,
+    /// Mean cycle length of its atoms from the temperature
+    pub mean_cycle: f64,
+}
+
+/// How two classes bond
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Compatibility {
+    /// Fraction of site pairs whose spikes can bond, see Signature::reactivity
+    pub reactivity: f64,
+    /// Bonds tried between random atoms of the two classes
+    pub tried: usize,
+    /// Tried bonds that were still Stable after calculating the pair together
+    pub held: usize,
+}
+
+/// The classes in order of site count then positive and negative sites, and <matrix[i][j]> how
+/// class i bonds with class j. The matrix is symmetric
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeriodicTable {
+    pub atoms: usize,
+    pub classes: Vec<Class>,
+    pub matrix: Vec<Vec<Compatibility>>,
+}
+
+impl PeriodicTable {
+    /// Header of the CSV matrix
+    pub const CSV_HEADER: &'static str = "class_a,class_b,reactivity,tried,held";
+
+    /// One row per pair of classes, class_a no later than class_b
+    pub fn csv_rows(&self) -> Vec<String> {
+        let mut rows = Vec::new();
+        for (i, row) in self.matrix.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate().skip(i) {
+                rows.push(format!(
+                    "{},{},{:.6},{},{}",
+                    self.classes[i].name,
+                    self.classes[j].name,
+                    cell.reactivity,
+                    cell.tried,
+                    cell.held
+                ));
+            }
+        }
+        rows
+    }
+}
+
+/// A calculated atom made from <def> with its bonding sites from <policy>, as a particle
+fn particle(def: &AtomDef, policy: &SitePolicy, temperature: Temperature) -> (Particle, u64) {
+    let mut rbn = def.to_rbn().expect("generated atoms are always valid");
+    rbn.set_site_policy(policy.clone());
+    let (cycle, _transient) = rbn
+        .calculate_particle(temperature, &mut NullRecorder)
+        .lengths();
+    let comp: Box<dyn Component> = Box::new(rbn);
+    (Particle::new(vec![comp]), cycle)
+}
+
+/// Spikes of every site of the atom in <particle>
+fn spikes(particle: &Particle) -> Vec<i32> {
+    (0..particle.bonding_sites.len())
+        .map(|site| particle.get_site_prop(0, site).unwrap_or(0))
+        .collect()
+}
+
+/// Generates the atoms of <config>, classes them and tries bonds between every pair of classes
+/// that can bond
+pub fn tabulate(config: &PeriodicConfig) -> PeriodicTable {
+    let mut members: BTreeMap<(usize, Signature), Members> = BTreeMap::new();
+    for idx in 0..config.atoms {
+        let mut rng = seeded(config.seed.wrapping_add(idx as u64));
+        let def = AtomDef::from_rbn(&RBN::new_with_rng(
+            config.k,
+            config.n,
+            config.bias,
+            &mut rng,
+        ));
+        let (atom, cycle) = particle(&def, &config.sites, config.temperature);
+        let sig = Signature::of(&spikes(&atom));
+        let entry = members
+            .entry((sig.sites(), sig))
+            .or_insert_with(|| (sig, vec![], vec![]));
+        entry.1.push(def);
+        entry.2.push(cycle);
+    }
+    let members: Vec<Members> = members.into_values().collect();
+
+    let mut rng = seeded(config.seed.wrapping_add(config.atoms as u64));
+    let count = members.len();
+    let mut matrix: Vec<Vec<Option<Compatibility>>> = vec![vec![None; count]; count];
+    for i in 0..count {
+        for j in i..count {
+            let reactivity = members[i].0.reactivity(&members[j].0);
+            let mut cell = Compatibility {
+                reactivity,
+                tried: 0,
+                held: 0,
+            };
+            if reactivity > 0.0 {
+                for _t in 0..config.trials {
+                    let def_a = members[i].1.choose(&mut rng).unwrap();
+                    let def_b = members[j].1.choose(&mut rng).unwrap();
+                    let (a, _cycle) = particle(def_a, &config.sites, config.temperature);
+                    let (b, _cycle) = particle(def_b, &config.sites, config.temperature);
+                    let (spikes_a, spikes_b) = (spikes(&a), spikes(&b));
+                    let mut pairs = Vec::new();
+                    for (sa, spike_a) in spikes_a.iter().enumerate() {
+                        for (sb, spike_b) in spikes_b.iter().enumerate() {
+                            if spikes_bond(*spike_a, *spike_b) {
+                                pairs.push((sa, sb));
+                            }
+                        }
+                    }
+                    let (sa, sb) = *pairs.choose(&mut rng).unwrap();
+                    let mut pair = a.bond_with((0, sa), b, (0, sb));
+                    cell.tried += 1;
+                    if pair
+                        .calculate_particle(config.temperature, &mut NullRecorder)
+                        .is_stable()
+                    {
+                        cell.held += 1;
+                    }
+                }
+            }
+            matrix[j][i] = Some(cell.clone());
+            matrix[i][j] = Some(cell);
+        }
+    }
+
+    PeriodicTable {
+        atoms: config.atoms,
+        classes: members
+            .iter()
+            .map(|(sig, defs, cycles)| Class {
+                name: sig.to_string(),
+                signature: *sig,
+                count: defs.len(),
+                mean_cycle: cycles.iter().sum::<u64>() as f64 / cycles.len() as f64,
+            })
+            .collect(),
+        matrix: matrix
+            .into_iter()
+            .map(|row| row.into_iter().map(|c| c.unwrap()).collect())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_of_random_atoms() {
+        let sig = Signature::of(&[3, -1, 0, 2]);
+        assert_eq!(sig.to_string(), "+2-1o1");
+        assert_eq!(sig.reactivity(&Signature::of(&[-4])), 0.5);
+        assert_eq!(sig.reactivity(&Signature::of(&[])), 0.0);
+
+        let config = PeriodicConfig {
+            atoms: 30,
+            n: 8,
+            k: 2,
+            bias: 0.5,
+            temperature: 0,
+            seed: 2,
+            sites: SitePolicy::default(),
+            trials: 5,
+        };
+        assert!(config.check().is_ok());
+        let table = tabulate(&config);
+        assert_eq!(table.classes.iter().map(|c| c.count).sum::<usize>(), 30);
+        let count = table.classes.len();
+        assert!(count > 1);
+        assert_eq!(table.matrix.len(), count);
+        for i in 0..count {
+            for j in 0..count {
+                let cell = &table.matrix[i][j];
+                assert_eq!(cell, &table.matrix[j][i]);
+                assert!(cell.held <= cell.tried);
+                assert_eq!(cell.tried > 0, cell.reactivity > 0.0);
+            }
+        }
+        assert!(table.matrix.iter().flatten().any(|c| c.tried > 0));
+        assert_eq!(table.csv_rows().len(), count * (count + 1) / 2);
+        assert_eq!(table, tabulate(&config));
+    }
+}
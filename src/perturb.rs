@@ -0,0 +1,160 @@
+//! Perturbation and knockout experiments. A Node is clamped, flipped or has a truth table entry
+//! flipped and the trajectory afterwards is compared with the unperturbed one: does the RBN find
+//! its way back to the same attractor and what happens to the cycle liveliness
+use crate::rbn::RBN;
+use crate::temp::{self, Temperature};
+use crate::trajectory::Trajectory;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Perturbation {
+    /// Node <node> always outputs <value>, a knockout for false and overexpression for true
+    Clamp { node: usize, value: bool },
+    /// The state of Node <node> is flipped once, <step> steps after the initial state
+    FlipState { node: usize, step: usize },
+    /// Entry <entry> of the truth table of Node <node> is flipped
+    FlipTable { node: usize, entry: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Response {
+    /// The perturbed trajectory ends on the attractor the unperturbed one did
+    pub same_attractor: bool,
+    /// Steps from the perturbation until the trajectory is on its attractor
+    pub recovery: usize,
+    pub cycle_before: usize,
+    pub cycle_after: usize,
+    /// Cycle liveliness of every Node on the attractor before and after
+    pub liveliness_before: Vec<i32>,
+    pub liveliness_after: Vec<i32>,
+}
+
+impl Response {
+    /// Change in the cycle liveliness of every Node
+    pub fn liveliness_change(&self) -> Vec<i32> {
+        self.liveliness_after
+            .iter()
+            .zip(&self.liveliness_before)
+            .map(|(after, before)| after - before)
+            .collect()
+    }
+}
+
+/// A perturbation and what it did
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trial {
+    pub perturbation: Perturbation,
+    pub response: Response,
+}
+
+/// Runs <rbn> from <temperature> with and without <perturbation>. State flips happen on the
+/// trajectory from <temperature>, clamped Nodes and flipped truth tables are applied to a copy of
+/// the RBN started from the first state of the original attractor. This overwrites the current
+/// state of <rbn>
+pub fn perturb(rbn: &RBN, temperature: Temperature, perturbation: Perturbation) -> Response {
+    let base = Trajectory::from_state(rbn, &temp::bits(temperature, rbn.get_node_count()));
+    let after = match perturbation {
+        Perturbation::FlipState { node, step } => {
+            let mut state = if step < base.states.len() {
+                base.states[step].clone()
+            } else {
+                base.cycle_states()[(step - base.transient) % base.cycle].clone()
+            };
+            state[node] = !state[node];
+            Trajectory::from_state(rbn, &state)
+        }
+        Perturbation::Clamp { node, value } => {
+            let copy = rbn.copy_structure();
+            let size = copy.get_function_table(node).len();
+            copy.set_function_table(node, vec![value; size]);
+            let mut state = base.cycle_states()[0].clone();
+            state[node] = value;
+            Trajectory::from_state(&copy, &state)
+        }
+        Perturbation::FlipTable { node, entry } => {
+            let copy = rbn.copy_structure();
+            let mut tbl = copy.get_function_table(node);
+            tbl[entry] = !tbl[entry];
+            copy.set_function_table(node, tbl);
+            Trajectory::from_state(&copy, &base.cycle_states()[0])
+        }
+    };
+    let before: HashSet<&Vec<bool>> = base.cycle_states().iter().collect();
+    let same_attractor = after.cycle == base.cycle
+        && after
+            .cycle_states()
+            .iter()
+            .all(|state| before.contains(state));
+    Response {
+        same_attractor,
+        recovery: after.transient,
+        cycle_before: base.cycle,
+        cycle_after: after.cycle,
+        liveliness_before: base.cycle_liveliness(),
+        liveliness_after: after.cycle_liveliness(),
+    }
+}
+
+/// Every single Node perturbation of <rbn>: each Node clamped off and on, each Node's state
+/// flipped once the trajectory from <temperature> is on its attractor and every truth table entry
+/// flipped
+pub fn scan(rbn: &RBN, temperature: Temperature) -> Vec<Trial> {
+    let n = rbn.get_node_count();
+    let on_cycle = Trajectory::from_state(rbn, &temp::bits(temperature, n)).transient;
+    let mut perturbations = Vec::new();
+    for node in 0..n {
+        perturbations.push(Perturbation::Clamp { node, value: false });
+        perturbations.push(Perturbation::Clamp { node, value: true });
+        perturbations.push(Perturbation::FlipState {
+            node,
+            step: on_cycle,
+        });
+        for entry in 0..rbn.get_function_table(node).len() {
+            perturbations.push(Perturbation::FlipTable { node, entry });
+        }
+    }
+    perturbations
+        .into_iter()
+        .map(|perturbation| Trial {
+            perturbation,
+            response: perturb(rbn, temperature, perturbation),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perturbations_of_a_known_network() {
+        // n0 = !n1, n1 = n0 is a 4 cycle whatever n2 = n2 is stuck at
+        let tbls = vec![vec![true, false], vec![false, true], vec![false, true]];
+        let rbn = RBN::new_from_def_k(tbls, vec![vec![1], vec![0], vec![2]]);
+        let flip = |p| perturb(&rbn, 0, p);
+        let moved = flip(Perturbation::FlipState { node: 0, step: 2 });
+        assert!(moved.same_attractor);
+        assert_eq!(moved.recovery, 0);
+        let switched = flip(Perturbation::FlipState { node: 2, step: 5 });
+        assert!(!switched.same_attractor);
+        assert_eq!(switched.liveliness_change(), vec![0, 0, 8]);
+        assert!(
+            flip(Perturbation::Clamp {
+                node: 2,
+                value: false
+            })
+            .same_attractor
+        );
+        let knock = flip(Perturbation::Clamp {
+            node: 1,
+            value: false,
+        });
+        assert!(!knock.same_attractor);
+        assert_eq!(knock.cycle_after, 1);
+        let table = flip(Perturbation::FlipTable { node: 2, entry: 0 });
+        assert!(!table.same_attractor);
+        assert_eq!(table.recovery, 1);
+        assert_eq!(scan(&rbn, 0).len(), 3 * (3 + 2));
+    }
+}
@@ -1,22 +1,24 @@
-use crate::node::Node;
-use crate::temp::Temperature;
+use crate::formats::dot::DOT_PALETTE;
+use crate::formats::json::AtomDef;
+use crate::node::{Node, NodeRef};
+use crate::particle::{Component, ComponentDef, Particle, Structure};
+use crate::temp::{self, Temperature};
 use crate::util::bonding::*;
 use crate::util::cycle_calc::*;
 use crate::util::formatters::IsFormatable;
-use particle::Component;
+use crate::util::grouping::SitePolicy;
+use crate::util::recorder::{Phase, Recorder};
 
 use bit_field::BitField;
 
 use rand::prelude::IteratorRandom;
 use rand::{thread_rng, Rng};
 
-use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
 use std::ptr;
-use std::rc::Rc;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RBNState {
     pattern: Vec<bool>,
 }
@@ -25,7 +27,7 @@ pub struct RBNState {
 //node index to the node pointer (short of exhaustive search which is expensive)
 #[derive(Debug, Clone)]
 pub struct RBNConnection {
-    node: Rc<RefCell<Node>>,
+    node: NodeRef,
     node_idx: usize,
     source_idx: Vec<usize>,
 }
@@ -43,34 +45,101 @@ pub struct RBN {
     /// change something in the RBN instance is and if you recalculated it
     cycle_len: Option<u64>,
     trans_len: Option<u64>,
+    /// Bonding sites from the last call to generate_bonding_sites
+    bonding_sites: Vec<BondingSite>,
+    /// How generate_bonding_sites turns the Nodes into bonding sites
+    site_policy: SitePolicy,
+}
+impl Component for RBN {
+    fn to_def(&self) -> ComponentDef {
+        ComponentDef::Atom(AtomDef::from_rbn(self))
+    }
+
+    fn get_atoms(&self) -> Vec<&RBN> {
+        vec![self]
+    }
+
+    fn get_bonded_sites(&self) -> Vec<(&BondingSite, &BondingSite)> {
+        vec![]
+    }
+
+    fn structure(&self) -> Structure {
+        Structure {
+            nodes: self.nodes.len(),
+            sites: self.bonding_sites.len(),
+            free_sites: self.bonding_sites.len(),
+            bonds: vec![],
+            components: vec![],
+        }
+    }
+
+    fn into_particle(self: Box<Self>) -> Particle {
+        Particle::new(vec![self])
+    }
+
+    fn fmt_dot_site(&self, prefix: &str, site: usize) -> (String, String) {
+        let sites = self.dot_sites();
+        let node = self.bonding_sites[site]
+            .get_interaction_list()
+            .iter()
+            .map(|nd| nd.borrow().get_id() as usize)
+            .find(|idx| sites[*idx][0] == site);
+        let node = match node {
+            Some(idx) => format!("{}n{}", prefix, idx),
+            None => format!("{}s{}", prefix, site),
+        };
+        (node, format!("cluster_{}s{}", prefix, site))
+    }
 }
-impl Component for RBN {}
 
 impl IsBondable for RBN {
+    /// Generates the bonding sites with the RBN's own SitePolicy, see set_site_policy
     fn generate_bonding_sites(&mut self) -> Vec<BondingSite> {
-        return self.generate_interaction_groups_inf(self.nodes.len() as u16, false);
+        self.bonding_sites = self.site_policy.bonding_sites(self);
+        self.bonding_sites.clone()
     }
-    /// Returns Bonding Property for a specific &BondingSite
-    /// If the BondingSite is not present on the particle returns None
+    fn generate_bonding_sites_with(&mut self, policy: &SitePolicy) -> Vec<BondingSite> {
+        self.bonding_sites = policy.bonding_sites(self);
+        self.bonding_sites.clone()
+    }
+    /// Returns Bonding Property for a specific &BondingSite, the sum of the cycle liveliness of
+    /// its Nodes (the spike). If the BondingSite is not present on the particle, or the particle
+    /// has not been calculated yet, returns None
     fn get_bonding_prop(&self, bs: &BondingSite) -> Option<i32> {
-        return None;
+        // liveliness is only there once the particle has been calculated
+        self.cycle_len?;
+        let mut spike = 0;
+        for nd in bs.get_interaction_list() {
+            let idx = nd.borrow().get_id() as usize;
+            match self.nodes.get(idx) {
+                Some(own) if NodeRef::ptr_eq(&own.node, nd) => {
+                    spike += own.node.borrow().get_cycle_liveliness()
+                }
+                _ => return None,
+            }
+        }
+        Some(spike)
     }
 
     /// Returns pointers to all BondingSites on the Particle
     fn get_all_bonding_sites(&self) -> Vec<&BondingSite> {
-        return vec![];
+        self.bonding_sites.iter().collect()
     }
 
-    /// Returns pointers to all BondingSites not currently part of a bond on the Particle
-    /// If there are no free sites returns None
+    /// Returns pointers to all BondingSites, bonds are kept by the Particle so an atom's sites are
+    /// all free. If there are no sites returns None
     fn get_free_bonding_sites(&self) -> Option<Vec<&BondingSite>> {
-        return None;
+        if self.bonding_sites.is_empty() {
+            None
+        } else {
+            Some(self.bonding_sites.iter().collect())
+        }
     }
 
-    /// Returns a random bonding site not currently part of a bond
-    /// If there are no free sites returns None
+    /// Returns a random bonding site, see get_free_bonding_sites
+    /// If there are no sites returns None
     fn get_rand_free_bonding_site(&self) -> Option<&BondingSite> {
-        return None;
+        self.bonding_sites.iter().choose(&mut thread_rng())
     }
 }
 ///
@@ -116,6 +185,52 @@ impl IsFormatable for RBN {
         }
         form_string
     }
+    /// Nodes are labelled with id, truth table and influence, edges go from input to Node and are
+    /// labelled with the table bit the input sets. Each bonding site is a filled cluster. A Node in
+    /// several sites is drawn in the first and its xlabel lists them all, a site left with no
+    /// Nodes of its own holds an invisible point for bonds to end on
+    fn fmt_dot(&self, prefix: &str) -> String {
+        let mut form_string = String::new();
+        let sites = self.dot_sites();
+        for (site_idx, site) in self.bonding_sites.iter().enumerate() {
+            form_string.push_str(&format!(
+                "subgraph cluster_{}s{} {{\nlabel=\"site {}\";\nstyle=filled;\nfillcolor=\"{}\";\n",
+                prefix,
+                site_idx,
+                site_idx,
+                DOT_PALETTE[site_idx % DOT_PALETTE.len()]
+            ));
+            let mut owned = false;
+            for nd in site.get_interaction_list() {
+                let idx = nd.borrow().get_id() as usize;
+                if sites[idx][0] == site_idx {
+                    form_string.push_str(&self.fmt_dot_node(prefix, idx, &sites[idx]));
+                    owned = true;
+                }
+            }
+            if !owned {
+                form_string.push_str(&format!(
+                    "{}s{} [shape=point, style=invis];\n",
+                    prefix, site_idx
+                ));
+            }
+            form_string.push_str("}\n");
+        }
+        for (idx, of) in sites.iter().enumerate() {
+            if of.is_empty() {
+                form_string.push_str(&self.fmt_dot_node(prefix, idx, of));
+            }
+        }
+        for idx in 0..self.nodes.len() {
+            for (bit, src) in self.nodes[idx].source_idx.iter().enumerate() {
+                form_string.push_str(&format!(
+                    "{}n{} -> {}n{} [label=\"{}\"];\n",
+                    prefix, src, prefix, idx, bit
+                ));
+            }
+        }
+        form_string
+    }
 }
 impl RBN {
     /// Create a new RBN with random structure
@@ -131,19 +246,26 @@ impl RBN {
             )
         }
 
+        RBN::new_with_rng(k, n, 0.5, &mut thread_rng())
+    }
+
+    /// Create a new RBN with random structure and functions drawn from <rng>, so the same seed
+    /// gives the same RBN. Any k a Node supports is allowed here
+    /// k : number of links per Node
+    /// n : number of Nodes
+    /// bias : probability of each truth table entry being true
+    pub fn new_with_rng<R: Rng + ?Sized>(k: u8, n: u16, bias: f64, rng: &mut R) -> RBN {
         let mut inv_nodes = Vec::<RBNConnection>::new();
         for x in 0..n {
             let connection = RBNConnection {
-                node: Rc::new(RefCell::new(Node::new(k, x))),
+                node: NodeRef::new(Node::new_with_rng(k, x, bias, rng)),
                 node_idx: x as usize,
                 source_idx: vec![],
             };
             // make the right number of nodes
             inv_nodes.push(connection);
         }
-        let mut rng = thread_rng();
         //let mut links = Vec::new();
-        let mut rnjesus = rand::thread_rng();
         for idx in 0..inv_nodes.len() {
             // for each node push the connections
             for _x in 0..k {
@@ -160,7 +282,7 @@ impl RBN {
             //hacky because I can't call inc_influence on myself so self inflece is tallied and
             //then updated in the loop below
             for c in inv_nodes[idx].node.borrow().inputs.iter() {
-                if !Rc::ptr_eq(&c, &inv_nodes[idx].node) {
+                if !NodeRef::ptr_eq(c, &inv_nodes[idx].node) {
                     c.borrow_mut().inc_influence();
                 } else {
                     self_inf += 1;
@@ -180,12 +302,29 @@ impl RBN {
             // inf_map: inf_map_temp,
             cycle_len: None,
             trans_len: None,
+            bonding_sites: vec![],
+            site_policy: SitePolicy::default(),
         }
     }
 
     ///Creates a new RBN with a predefined structure. Nodes defined by truth tables in <nd_tbls>
     ///links defined by indexes in strct_tbl
     pub fn new_from_def(nd_tbls: Vec<Vec<bool>>, strct_tbl: Vec<(usize, usize)>) -> RBN {
+        if nd_tbls.len() != strct_tbl.len() {
+            panic!(
+                "Length mismatch number of Nodes = {}, structure table lenght = {}\n",
+                nd_tbls.len(),
+                strct_tbl.len()
+            );
+        }
+        let inputs = strct_tbl.iter().map(|lnk| vec![lnk.0, lnk.1]).collect();
+        RBN::new_from_def_k(nd_tbls, inputs)
+    }
+
+    ///Creates a new RBN with a predefined structure where each Node can have any number of
+    ///inputs. Node <i> has truth table <nd_tbls[i]> and takes its inputs from the indexes in
+    ///<strct_tbl[i]>, the first input being the least significant bit of the table lookup
+    pub fn new_from_def_k(nd_tbls: Vec<Vec<bool>>, strct_tbl: Vec<Vec<usize>>) -> RBN {
         if nd_tbls.len() != strct_tbl.len() {
             panic!(
                 "Length mismatch number of Nodes = {}, structure table lenght = {}\n",
@@ -196,30 +335,32 @@ impl RBN {
         let mut inv_nodes = Vec::<RBNConnection>::new();
         let mut id = 0;
         for tbl in nd_tbls.into_iter() {
+            if tbl.len() != 1 << strct_tbl[id as usize].len() {
+                panic!(
+                    "Node {} has {} inputs but a truth table of size {}\n",
+                    id,
+                    strct_tbl[id as usize].len(),
+                    tbl.len()
+                );
+            }
             let connection = RBNConnection {
-                node: Rc::new(RefCell::new(Node::new_with_tbl(tbl, id))),
+                node: NodeRef::new(Node::new_with_tbl(tbl, id)),
                 node_idx: id as usize,
-                source_idx: vec![],
+                source_idx: strct_tbl[id as usize].clone(),
             };
             inv_nodes.push(connection);
             id += 1
         }
         //let mut links = Vec::new();
         for idx in 0..inv_nodes.len() {
-            inv_nodes[idx]
-                .node
-                .borrow_mut()
-                .inputs
-                .push(inv_nodes[strct_tbl[idx].0].node.clone());
-            inv_nodes[idx]
-                .node
-                .borrow_mut()
-                .inputs
-                .push(inv_nodes[strct_tbl[idx].1].node.clone());
+            for src in &strct_tbl[idx] {
+                let input = inv_nodes[*src].node.clone();
+                inv_nodes[idx].node.borrow_mut().inputs.push(input);
+            }
             let mut self_inf = 0;
             //itterate over node inputs and increment the input node's influece map
             for c in inv_nodes[idx].node.borrow().inputs.iter() {
-                if !Rc::ptr_eq(&c, &inv_nodes[idx].node) {
+                if !NodeRef::ptr_eq(c, &inv_nodes[idx].node) {
                     c.borrow_mut().inc_influence();
                 } else {
                     self_inf += 1;
@@ -241,26 +382,171 @@ impl RBN {
             nodes: inv_nodes,
             cycle_len: None,
             trans_len: None,
+            bonding_sites: vec![],
+            site_policy: SitePolicy::default(),
         }
     }
-    fn set_state(&self, state: &RBNState) {
-        let mut idx = 0;
+
+    fn fmt_dot_node(&self, prefix: &str, idx: usize, sites: &[usize]) -> String {
+        let nd = self.nodes[idx].node.borrow();
+        let mut tbl = String::new();
+        for val in nd.get_function_table() {
+            tbl.push_str(&format!("{}", *val as u8));
+        }
+        let shared = if sites.len() > 1 {
+            let names: Vec<String> = sites.iter().map(|s| s.to_string()).collect();
+            format!(", xlabel=\"sites {}\"", names.join(" "))
+        } else {
+            String::new()
+        };
+        format!(
+            "{}n{} [label=\"{}\\n{}\\ninf {}\"{}];\n",
+            prefix,
+            idx,
+            nd.get_id(),
+            tbl,
+            nd.get_influence().unwrap_or(0),
+            shared
+        )
+    }
+
+    /// Bonding sites each Node is part of in order, a Node is drawn in the cluster of the first
+    fn dot_sites(&self) -> Vec<Vec<usize>> {
+        let mut sites = vec![vec![]; self.nodes.len()];
+        for (site_idx, site) in self.bonding_sites.iter().enumerate() {
+            for nd in site.get_interaction_list() {
+                let of: &mut Vec<usize> = &mut sites[nd.borrow().get_id() as usize];
+                if !of.contains(&site_idx) {
+                    of.push(site_idx);
+                }
+            }
+        }
+        sites
+    }
+
+    /// Number of Nodes in the RBN
+    pub fn get_node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Policy generate_bonding_sites follows from now on
+    pub fn set_site_policy(&mut self, policy: SitePolicy) {
+        self.site_policy = policy;
+    }
+
+    pub fn get_site_policy(&self) -> &SitePolicy {
+        &self.site_policy
+    }
+
+    /// Shared handle to the Node at index <idx>
+    pub fn get_node(&self, idx: usize) -> NodeRef {
+        self.nodes[idx].node.clone()
+    }
+
+    /// Truth table of the Node at index <idx>
+    pub fn get_function_table(&self, idx: usize) -> Vec<bool> {
+        self.nodes[idx].node.borrow().get_function_table().clone()
+    }
+
+    /// Indexes of the Nodes that are inputs to the Node at index <idx>, in table lookup order
+    pub fn get_node_inputs(&self, idx: usize) -> &Vec<usize> {
+        &self.nodes[idx].source_idx
+    }
+
+    /// Current state of every Node, index i is Node i
+    pub fn get_current_states(&self) -> Vec<bool> {
+        self.nodes
+            .iter()
+            .map(|n| n.node.borrow().get_current_state())
+            .collect()
+    }
+
+    /// New RBN with the same Nodes, functions, wiring and site policy as this one, nothing is
+    /// calculated and there are no bonding sites
+    pub fn copy_structure(&self) -> RBN {
+        let n = self.nodes.len();
+        let mut copy = RBN::new_from_def_k(
+            (0..n).map(|idx| self.get_function_table(idx)).collect(),
+            (0..n)
+                .map(|idx| self.nodes[idx].source_idx.clone())
+                .collect(),
+        );
+        for idx in 0..n {
+            copy.set_node_functions(idx, self.get_node_functions(idx));
+        }
+        copy.set_site_policy(self.site_policy.clone());
+        copy
+    }
+
+    /// Makes Node <idx> probabilistic, see Node::set_functions
+    pub fn set_node_functions(&self, idx: usize, fns: Vec<(Vec<bool>, f64)>) {
+        self.nodes[idx].node.borrow_mut().set_functions(fns);
+    }
+
+    /// Candidate truth tables and probabilities of Node <idx>, empty for an ordinary Node
+    pub fn get_node_functions(&self, idx: usize) -> Vec<(Vec<bool>, f64)> {
+        self.nodes[idx].node.borrow().get_functions().clone()
+    }
+
+    /// True if any Node chooses its truth table at random, such an RBN has no cycle to detect
+    pub fn is_probabilistic(&self) -> bool {
+        self.nodes
+            .iter()
+            .any(|n| n.node.borrow().is_probabilistic())
+    }
+
+    /// Step of a probabilistic RBN, every probabilistic Node chooses its truth table from <rng>
+    /// before the usual step
+    pub fn step_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> RBNState {
         for n in &self.nodes {
-            n.node.borrow_mut().set_current_state(state.pattern[idx]);
-            idx += 1;
+            n.node.borrow_mut().choose_function(rng);
+        }
+        self.step()
+    }
+
+    /// Step with noise, after the next state of every Node is calculated each one flips with
+    /// probability <eta>. Probabilistic Nodes choose their truth table from <rng> as in
+    /// step_with_rng. Returns the next state including the flips
+    pub fn step_with_noise<R: Rng + ?Sized>(&self, eta: f64, rng: &mut R) -> RBNState {
+        let mut state = self.step_with_rng(rng);
+        for (idx, n) in self.nodes.iter().enumerate() {
+            if rng.gen_bool(eta) {
+                n.node.borrow_mut().flip_next_state();
+                state.pattern[idx] = !state.pattern[idx];
+            }
+        }
+        state
+    }
+
+    /// Sets the current state of Node <idx> only
+    pub fn set_node_state(&self, idx: usize, state: bool) {
+        self.nodes[idx].node.borrow_mut().set_current_state(state);
+    }
+
+    /// Replaces the truth table of Node <idx>, see Node::set_function_table
+    pub fn set_function_table(&self, idx: usize, tbl: Vec<bool>) {
+        self.nodes[idx].node.borrow_mut().set_function_table(tbl);
+    }
+
+    /// Sets the current state of every Node from <state>, Node i takes bit i. Nodes past the end
+    /// of <state> are set off, so a Temperature can start an RBN of more than 16 Nodes
+    pub fn set_state(&self, state: &RBNState) {
+        for (idx, n) in self.nodes.iter().enumerate() {
+            let bit = state.pattern.get(idx).cloned().unwrap_or(false);
+            n.node.borrow_mut().set_current_state(bit);
         }
     }
     /// Generates interaction groups based on the influence map.
     /// If is_least_inf is true then least influencial is first
     /// TODO Need to test that influence ordersing are exactly opposite (ie equivelent numbers are
     /// accessed in the same order
-    fn generate_interaction_groups_inf(
+    pub fn generate_interaction_groups_inf(
         &self,
         max_group_size: u16,
         is_least_inf: bool,
     ) -> Vec<BondingSite> {
         // gen a working copy of the node list
-        let mut nds_tmp = HashMap::<usize, Rc<RefCell<Node>>>::new();
+        let mut nds_tmp = HashMap::<usize, NodeRef>::new();
         // generate influence set
         // (nodes_idx, influence)
         let mut inf_set_tmp = Vec::<(usize, u16)>::new();
@@ -288,7 +574,7 @@ impl RBN {
                 inf_set_tmp.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0))); // inf_set_tmp[0] is most influencial, if equal biggest id is first
             }
             //println!("{:?}", inf_set_tmp);
-            let mut interaction_group = Vec::<Rc<RefCell<Node>>>::new();
+            let mut interaction_group = Vec::<NodeRef>::new();
             let mut current_node_idx = inf_set_tmp.pop().unwrap().0; // take a node from the ordered list
                                                                      //println!("current node {}", current_node_idx);
             let mut current_ig_size = 1;
@@ -299,45 +585,30 @@ impl RBN {
                 current_ig_size += 1;
                 //remove the node from the working list
                 nds_tmp.remove(&current_node_idx);
-                // get the input with the most influence
-                let next_idx = self.nodes[current_node_idx]
+                // walk the inputs from the most (or least) influential and take the first one
+                // still in the list, constant Nodes have nowhere to go so end the group
+                let mut inputs = self.nodes[current_node_idx]
                     .node
                     .borrow()
-                    .get_input_by_inf(is_least_inf)
-                    .expect("Node has no inputs")
-                    .borrow()
-                    .get_id() as usize;
-                //println!("\t Cur: {} Next node: {}", current_node_idx, next_idx);
-                // if that input is still in the list
-                if nds_tmp.contains_key(&next_idx) {
-                    // select it as next node
-                    current_node_idx = next_idx;
-                    //add it to the interaciton group
-                    interaction_group.push(nds_tmp[&current_node_idx].clone());
-                    //remove the node from the list of available nodes
-                    nds_tmp.remove(&next_idx);
-                } else {
-                    // if the most influential is no longer in the list take the other
-                    let next_idx = self.nodes[current_node_idx]
-                        .node
-                        .borrow()
-                        .get_input_by_inf(!is_least_inf)
-                        .expect("Node has no inputs")
-                        .borrow()
-                        .get_id() as usize;
-                    // println!("\t\t Visited, trying: {}", next_idx);
-                    if nds_tmp.contains_key(&next_idx) {
+                    .get_inputs_by_inf();
+                if !is_least_inf {
+                    inputs.reverse();
+                }
+                let next = inputs
+                    .iter()
+                    .map(|input| input.borrow().get_id() as usize)
+                    .find(|idx| nds_tmp.contains_key(idx));
+                match next {
+                    Some(next_idx) => {
                         // select it as next node
                         current_node_idx = next_idx;
                         //add it to the interaciton group
                         interaction_group.push(nds_tmp[&current_node_idx].clone());
                         //remove the node from the list of available nodes
                         nds_tmp.remove(&next_idx);
-                    } else {
-                        // println!("\t\t Both Missing , end of list");
-                        // if both are missing then end the interaction_group
-                        break;
                     }
+                    // if every input is taken then end the interaction_group
+                    None => break,
                 }
             }
             // interaction_group is full
@@ -356,29 +627,23 @@ impl RBN {
             // println!("{:?}", inf_set_tmp);
         }
         // println!("{}", ig_set.len());
-        for bond in &ig_set {
-            println!("{}", bond)
-        }
-
         return ig_set;
     }
 
-    fn calculate_cycle_ln(&mut self, init_state: Temperature, verbose: bool) -> u64 {
+    fn calculate_cycle_ln(&mut self, init_state: &RBNState, rec: &mut dyn Recorder) -> u64 {
         let mut hare: RBNState;
         let mut tortoise: RBNState;
         let mut cycle_count = 1;
         let mut power = 1;
 
-        tortoise = RBNState::from(init_state);
+        tortoise = init_state.clone();
+        let mut steps = 0;
         self.set_state(&tortoise);
-        if verbose {
-            println!("{}", self.fmt_state());
-        }
+        rec.record_state(Phase::CycleSearch, steps, &self.get_current_states());
         hare = self.step();
         self.sync();
-        if verbose {
-            println!("{}", self.fmt_state());
-        }
+        steps += 1;
+        rec.record_state(Phase::CycleSearch, steps, &self.get_current_states());
         while tortoise != hare {
             if power == cycle_count {
                 tortoise = hare;
@@ -387,16 +652,16 @@ impl RBN {
             }
             hare = self.step();
             self.sync();
-            if verbose {
-                println!("{}", self.fmt_state());
-            }
+            steps += 1;
+            rec.record_state(Phase::CycleSearch, steps, &self.get_current_states());
             cycle_count += 1;
         }
         self.cycle_len = Some(cycle_count);
+        rec.record_event("cycle_length", &cycle_count.to_string());
         return cycle_count;
     }
 
-    fn calculate_transient_ln(&mut self, init_state: Temperature, verbose: bool) -> u64 {
+    fn calculate_transient_ln(&mut self, init_state: &RBNState, rec: &mut dyn Recorder) -> u64 {
         let mut cl;
         if self.cycle_len.is_some() {
             cl = self.cycle_len.unwrap();
@@ -405,8 +670,8 @@ impl RBN {
         }
         let mut hare: RBNState;
         let mut tortoise: RBNState;
-        hare = RBNState::from(init_state);
-        tortoise = RBNState::from(init_state);
+        hare = init_state.clone();
+        tortoise = init_state.clone();
         self.set_state(&hare);
         //Put hare 1 cl away from tortose
         for _idx in 0..cl {
@@ -422,6 +687,7 @@ impl RBN {
             mu += 1;
         }
         self.trans_len = Some(mu);
+        rec.record_event("transient_length", &mu.to_string());
         return mu;
     }
     fn update_node_trans_liveliness(&self) {
@@ -439,7 +705,7 @@ impl RBN {
             n.node.borrow_mut().reset_liveliness();
         }
     }
-    fn calculate_liveliness(&self, init_state: Temperature, verbose: bool) {
+    fn calculate_liveliness(&self, init_state: &RBNState, rec: &mut dyn Recorder) {
         let mut cl;
         let mut mu;
         if self.cycle_len.is_some() && self.trans_len.is_some() {
@@ -450,39 +716,78 @@ impl RBN {
         }
         self.reset_node_liveliness();
 
-        self.set_state(&RBNState::from(init_state));
-        self.update_node_trans_liveliness();
-        if verbose {
-            println!("-------------------------- \n Transient");
-            println!("{}", self.fmt_state());
+        self.set_state(init_state);
+        if mu > 0 {
+            self.update_node_trans_liveliness();
+            rec.record_state(Phase::Transient, 0, &self.get_current_states());
         }
-        for _idx in 1..mu {
+        for idx in 1..mu {
             // starting from 1 because set_state above is the first in transient
             self.step();
             self.sync();
             self.update_node_trans_liveliness();
-            if verbose {
-                println!("{}", self.fmt_state());
-            }
+            rec.record_state(Phase::Transient, idx, &self.get_current_states());
         }
-        if verbose {
-            println!("-------------------------- \n Cycle");
-        }
-        for _idx in 0..cl {
-            self.step();
-            self.sync();
-            self.update_node_cycle_liveliness();
-            if verbose {
-                println!("{}", self.fmt_state());
+        rec.record_liveliness(Phase::Transient, &self.get_liveliness(Phase::Transient));
+        for idx in 0..cl {
+            // with no transient the initial state is the first of the cycle
+            if mu > 0 || idx > 0 {
+                self.step();
+                self.sync();
             }
+            self.update_node_cycle_liveliness();
+            rec.record_state(Phase::Cycle, mu + idx, &self.get_current_states());
+        }
+        rec.record_liveliness(Phase::Cycle, &self.get_liveliness(Phase::Cycle));
+    }
+
+    /// Sets the liveliness of every Node, index i is Node i, for an RBN whose dynamics were run as
+    /// part of a larger network
+    pub fn set_liveliness(&self, trans: &[i32], cycle: &[i32]) {
+        for ((n, t), c) in self.nodes.iter().zip(trans).zip(cycle) {
+            n.node.borrow_mut().set_liveliness(*t, *c);
         }
     }
+
+    /// Runs the dynamics from <init_state> as calculate_particle does from a Temperature,
+    /// returning the cycle and transient lengths. <init_state> is read as set_state reads it, so
+    /// it can be shorter or longer than the RBN
+    pub fn calculate_from(&mut self, init_state: &RBNState, rec: &mut dyn Recorder) -> (u64, u64) {
+        // pad to one entry per Node so the state compares equal to states from step
+        let init_state = RBNState {
+            pattern: (0..self.nodes.len())
+                .map(|idx| init_state.pattern.get(idx).cloned().unwrap_or(false))
+                .collect(),
+        };
+        let cl = self.calculate_cycle_ln(&init_state, rec);
+        let tran = self.calculate_transient_ln(&init_state, rec);
+        self.calculate_liveliness(&init_state, rec);
+        (cl, tran)
+    }
+
+    /// Whether every Node has its liveliness, so get_liveliness can be called
+    pub fn is_calculated(&self) -> bool {
+        self.nodes.iter().all(|n| n.node.borrow().has_liveliness())
+    }
+
+    /// Liveliness of every Node from the last calculate_particle, index i is Node i
+    pub fn get_liveliness(&self, phase: Phase) -> Vec<i32> {
+        self.nodes
+            .iter()
+            .map(|n| match phase {
+                Phase::Cycle => n.node.borrow().get_cycle_liveliness(),
+                _ => n.node.borrow().get_trans_liveliness(),
+            })
+            .collect()
+    }
 }
 
 impl IsSynchronous for RBN {
-    /// Update Nodes for next time step
+    /// Update Nodes for next time step, returning the next state with one entry per Node
     fn step(&self) -> RBNState {
-        let mut state = RBNState::from(0 as u64);
+        let mut state = RBNState {
+            pattern: vec![false; self.nodes.len()],
+        };
         let mut idx = 0;
         for nds in &self.nodes {
             // get current state of inputs:
@@ -490,14 +795,11 @@ impl IsSynchronous for RBN {
             // we can borrow more then once) if either the node borrow or the input borrow is
             // borrow_mut() the thing will panic at runtime
             // Test case panics on inputs[1] if there is a borrow_mut()
-            let l = nds.node.borrow().inputs[0].borrow().get_current_state();
-            let r = nds.node.borrow().inputs[1].borrow().get_current_state();
             let mut sum = 0;
-            if l {
-                sum += 1;
-            }
-            if r {
-                sum += 2;
+            for (bit, input) in nds.node.borrow().inputs.iter().enumerate() {
+                if input.borrow().get_current_state() {
+                    sum += 1 << bit;
+                }
             }
             state.pattern[idx] = nds.node.borrow_mut().calc_next_state(sum);
             idx += 1;
@@ -514,14 +816,20 @@ impl IsSynchronous for RBN {
 }
 
 impl IsSubSymbolic for RBN {
-    fn calculate_particle(&mut self, init_state: Temperature, verbose: bool) -> Stability {
-        let cl = self.calculate_cycle_ln(init_state, verbose);
-        let tran = self.calculate_transient_ln(init_state, verbose);
-        self.calculate_liveliness(init_state, verbose);
-        return Stability::Unstable {
+    /// An atom has no bonds to lose, so it is always Stable
+    fn calculate_particle(&mut self, init_state: Temperature, rec: &mut dyn Recorder) -> Stability {
+        assert!(
+            !self.is_probabilistic(),
+            "a probabilistic RBN has no single cycle to calculate"
+        );
+        let (cl, tran) = self.calculate_from(
+            &RBNState::from(temp::bits(init_state, self.nodes.len())),
+            rec,
+        );
+        Stability::Stable {
             cycle: cl,
             transient: tran,
-        };
+        }
     }
 }
 
@@ -552,6 +860,22 @@ impl From<u64> for RBNState {
         RBNState { pattern: pat }
     }
 }
+/// Node i takes <pattern[i]>, for RBNs too large for the integer conversions
+impl From<Vec<bool>> for RBNState {
+    fn from(pattern: Vec<bool>) -> Self {
+        RBNState { pattern }
+    }
+}
+/// Packs the first 64 Node states into an integer, Node i is bit i
+impl<'a> From<&'a RBNState> for u64 {
+    fn from(state: &'a RBNState) -> Self {
+        let mut num = 0u64;
+        for (idx, val) in state.pattern.iter().take(64).enumerate() {
+            num.set_bit(idx, *val);
+        }
+        num
+    }
+}
 impl PartialEq for RBNState {
     fn eq(&self, other: &Self) -> bool {
         self.pattern == other.pattern
@@ -567,10 +891,12 @@ impl fmt::Display for RBN {
             for val in n.node.borrow().get_function_table() {
                 form_string.push_str(&format!("{},", *val as u8));
             }
+            form_string.push('\t');
+            for input in &n.node.borrow().inputs {
+                form_string.push_str(&format!("{},", input.borrow().get_id()));
+            }
             form_string.push_str(&format!(
-                "\t{},{},\t{}\n",
-                n.node.borrow().inputs[0].borrow().get_id(),
-                n.node.borrow().inputs[1].borrow().get_id(),
+                "\t{}\n",
                 n.node.borrow().get_influence().unwrap_or(0)
             ));
         }
@@ -581,6 +907,9 @@ impl fmt::Display for RBN {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::trajectory::Trajectory;
+    use crate::util::recorder::NullRecorder;
+    use crate::util::rng::seeded;
     #[test]
     fn make_from_tbl() {
         let mut tbl = Vec::new();
@@ -701,7 +1030,7 @@ mod tests {
 
         println!("{}", newrbn);
         println!("{}", newrbn.fmt_header());
-        newrbn.calculate_particle(0b000000000101, true);
+        newrbn.calculate_particle(0b000000000101, &mut NullRecorder);
         let bonding_sites = newrbn.generate_interaction_groups_inf(23, false);
         let mut generated_sites = String::new();
         for bonding_site in bonding_sites {
@@ -734,6 +1063,39 @@ mod tests {
         let expected_sites = "[4][5][3][11][8][2][0][10][9][6][1][7]";
         assert_eq!(generated_sites, expected_sites);
     }
+    #[test]
+    fn interaction_groups_k3() {
+        // Nodes 1, 2 and 3 all have influence 2 and are wired into Node 0 in reverse id order
+        let tbls = vec![vec![false; 8]; 5];
+        let strct = vec![
+            vec![3, 2, 1],
+            vec![4, 4, 4],
+            vec![4, 4, 4],
+            vec![0, 0, 0],
+            vec![1, 2, 3],
+        ];
+        let rbn = RBN::new_from_def_k(tbls, strct);
+        let ids: Vec<u16> = rbn
+            .get_node(0)
+            .borrow()
+            .get_inputs_by_inf()
+            .iter()
+            .map(|input| input.borrow().get_id())
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+
+        let sites = |least: bool| -> String {
+            rbn.generate_interaction_groups_inf(4, least)
+                .iter()
+                .map(|site| format!("{}", site))
+                .collect()
+        };
+        // from Node 0 the most influential input 3 is taken, so the walk goes on to 2
+        assert_eq!(sites(false), "[4, 3, 0, 2][1]");
+        // from Node 4 the least influential input 1 is taken, so the walk goes on to 2
+        assert_eq!(sites(true), "[1, 4, 2][3, 0]");
+    }
+
     #[test]
     fn test_rbn_calc() {
         let mut nds = Vec::new();
@@ -827,7 +1189,7 @@ mod tests {
 
         println!("{}", newrbn);
         println!("{}", newrbn.fmt_header());
-        newrbn.calculate_particle(0b000000000101, true);
+        newrbn.calculate_particle(0b000000000101, &mut NullRecorder);
         //This generates a cycle length of 4
         assert_eq!(Some(4), newrbn.cycle_len);
         assert_eq!(Some(5), newrbn.trans_len);
@@ -844,6 +1206,114 @@ mod tests {
         assert_eq!(tl_str, expected_tl);
     }
 
+    /// Random RBNs for the property tests, case <case> is generated from seed <case> so a failure
+    /// can be reproduced on its own. Sizes stay small enough for chaotic networks to settle
+    fn random_case(case: u64) -> (RBN, Temperature) {
+        let mut rng = seeded(case);
+        let k = rng.gen_range(1, 5);
+        let n = rng.gen_range(k as u16, 15);
+        let bias = rng.gen_range(0.2, 0.8);
+        (RBN::new_with_rng(k, n, bias, &mut rng), rng.gen())
+    }
+
+    #[test]
+    fn random_rbn_properties() {
+        for case in 0..200 {
+            let (mut rbn, temperature) = random_case(case);
+            let n = rbn.get_node_count();
+            let (cycle, transient) = rbn
+                .calculate_particle(temperature, &mut NullRecorder)
+                .lengths();
+            assert!(cycle >= 1, "case {}: cycle {}", case, cycle);
+            if transient == 0 {
+                assert!(
+                    rbn.get_liveliness(Phase::Transient).iter().all(|l| *l == 0),
+                    "case {}: transient liveliness with no transient",
+                    case
+                );
+            }
+
+            // transient + cycle reproduces the trajectory: the state after <transient> steps
+            // comes back <cycle> steps later and no sooner, and the one before it never does
+            let start = temp::bits(temperature, n);
+            assert_eq!(
+                Trajectory::record(&mut rbn, temperature),
+                Trajectory::from_state(&rbn, &start),
+                "case {}",
+                case
+            );
+            rbn.set_state(&RBNState::from(start));
+            let mut trajectory = vec![rbn.get_current_states()];
+            for _step in 0..transient + cycle {
+                rbn.step();
+                rbn.sync();
+                trajectory.push(rbn.get_current_states());
+            }
+            let (transient, cycle) = (transient as usize, cycle as usize);
+            let on_cycle = &trajectory[transient];
+            assert_eq!(on_cycle, &trajectory[transient + cycle], "case {}", case);
+            for state in &trajectory[transient + 1..transient + cycle] {
+                assert_ne!(on_cycle, state, "case {}: cycle too long", case);
+            }
+            if transient > 0 {
+                assert_ne!(
+                    trajectory[transient - 1],
+                    trajectory[transient - 1 + cycle],
+                    "case {}: transient too long",
+                    case
+                );
+            }
+
+            // stepping <cycle> times from any cycle state returns to it
+            rbn.set_state(&RBNState::from(trajectory[transient + cycle - 1].clone()));
+            for _step in 0..cycle {
+                rbn.step();
+                rbn.sync();
+            }
+            assert_eq!(
+                rbn.get_current_states(),
+                trajectory[transient + cycle - 1],
+                "case {}",
+                case
+            );
+
+            let liveliness: i32 = rbn
+                .get_liveliness(Phase::Cycle)
+                .iter()
+                .map(|l| l.abs())
+                .sum();
+            assert!(
+                liveliness as usize <= cycle * n,
+                "case {}: liveliness {} over {} states of {} Nodes",
+                case,
+                liveliness,
+                cycle,
+                n
+            );
+
+            // interaction groups partition the Nodes
+            for size in 1..=n as u16 {
+                for least in &[false, true] {
+                    let mut ids: Vec<u16> = rbn
+                        .generate_interaction_groups_inf(size, *least)
+                        .iter()
+                        .flat_map(|site| site.get_interaction_list().iter())
+                        .map(|node| node.borrow().get_id())
+                        .collect();
+                    ids.sort();
+                    assert_eq!(
+                        ids,
+                        (0..n as u16).collect::<Vec<u16>>(),
+                        "case {}: groups of {} (least influential {})",
+                        case,
+                        size,
+                        least
+                    );
+                }
+            }
+        }
+    }
+
     #[test]
     #[should_panic(expected = "Node lookup out of range")]
     fn get_state_oversize() {
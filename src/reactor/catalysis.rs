@@ -0,0 +1,322 @@
+//! Catalysis screen. Every reaction A + B between a reactor's species is tried alone and with a
+//! third species C bonded to one of the reactants for the length of the reaction. The bond couples
+//! C's dynamics to the reactant's (see Particle::composite), which can change the spikes of the
+//! reactant's other sites and so which of them bond. A C whose dynamics change the rate or the
+//! products of a reaction is recorded as a catalyst, or an inhibitor, of it
+use super::stats::species_name;
+use super::{generate_species, make_atom, ReactorConfig};
+use crate::formats::json::AtomDef;
+use crate::particle::Particle;
+use crate::temp::Temperature;
+use crate::util::bonding::{spikes_bond, IsSubSymbolic};
+use crate::util::recorder::NullRecorder;
+use crate::util::rng::seeded;
+use std::collections::BTreeMap;
+
+/// A free site, (component, site), and its spike
+type SiteSpike = ((usize, usize), Option<i32>);
+
+/// What happens when two particles meet at a random free site each
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Outcome {
+    /// Fraction of the site pairs that bond, the chance a collision is a reaction
+    pub rate: f64,
+    /// Species of each product, see stats::species_name, with the number of site pairs making it
+    pub products: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    /// The reaction is more likely
+    Accelerates,
+    /// The reaction is less likely
+    Inhibits,
+    /// The reaction makes different products
+    Redirects,
+}
+
+/// A way a catalyst changes a reaction. Taking up a site changes a reaction on its own, so the
+/// effects are those of the catalyst's dynamics: the outcome with the catalyst bonded against the
+/// outcome with the same site blocked
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Catalysis {
+    /// Species index of the catalyst
+    pub catalyst: usize,
+    /// Reactant the catalyst bonds to, 0 for A and 1 for B
+    pub host: usize,
+    /// Site of the host and site of the catalyst that bond
+    pub sites: (usize, usize),
+    /// Outcome with the host's site blocked
+    pub blocked: Outcome,
+    /// Outcome with the catalyst bonded
+    pub outcome: Outcome,
+    pub effects: Vec<Effect>,
+}
+
+/// Reaction A + B alone and every catalysis of it found
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Screen {
+    pub a: usize,
+    pub b: usize,
+    pub alone: Outcome,
+    pub catalysis: Vec<Catalysis>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CatalysisReport {
+    /// Species name of each species index
+    pub species: Vec<String>,
+    pub reactions: Vec<Screen>,
+}
+
+/// The free sites of the first <comps> components of <particle> with their spikes
+fn free_spikes(particle: &Particle, comps: usize) -> Vec<SiteSpike> {
+    particle
+        .get_free_sites()
+        .into_iter()
+        .filter(|site| site.0 < comps)
+        .map(|site| (site, particle.get_site_prop(site.0, site.1)))
+        .collect()
+}
+
+/// Outcome of a particle made by <make_a> meeting one made by <make_b>, the spikes of their free
+/// sites being <spikes_a> and <spikes_b>. Products are bonded from fresh particles
+fn outcome(
+    make_a: &dyn Fn() -> Particle,
+    spikes_a: &[SiteSpike],
+    make_b: &dyn Fn() -> Particle,
+    spikes_b: &[SiteSpike],
+) -> Outcome {
+    let mut products = BTreeMap::new();
+    let mut bonding = 0;
+    for (site_a, spike_a) in spikes_a {
+        for (site_b, spike_b) in spikes_b {
+            match (spike_a, spike_b) {
+                (Some(sa), Some(sb)) if spikes_bond(*sa, *sb) => {
+                    let product = make_a().bond_with(*site_a, make_b(), *site_b);
+                    *products.entry(species_name(&product)).or_insert(0) += 1;
+                    bonding += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+    let pairs = spikes_a.len() * spikes_b.len();
+    Outcome {
+        rate: if pairs == 0 {
+            0.0
+        } else {
+            bonding as f64 / pairs as f64
+        },
+        products,
+    }
+}
+
+/// Spikes of the free sites of <host> while <catalyst> is bonded to it between <host_site> and
+/// <catalyst_site>, after running their dynamics together from <temperature>. None if the
+/// bond would not hold
+fn catalysed_spikes(
+    host: Particle,
+    host_site: (usize, usize),
+    catalyst: Particle,
+    catalyst_site: (usize, usize),
+    temperature: Temperature,
+) -> Option<Vec<SiteSpike>> {
+    let comps = host.components.len();
+    let mut complex = host.bond_with(host_site, catalyst, catalyst_site);
+    if complex
+        .calculate_particle(temperature, &mut NullRecorder)
+        .is_stable()
+    {
+        Some(free_spikes(&complex, comps))
+    } else {
+        None
+    }
+}
+
+/// Ways a catalyst changes <blocked>, the outcome with the host's site merely taken, into
+/// <catalysed>
+fn effects(blocked: &Outcome, catalysed: &Outcome) -> Vec<Effect> {
+    let mut effects = Vec::new();
+    if catalysed.rate > blocked.rate {
+        effects.push(Effect::Accelerates);
+    } else if catalysed.rate < blocked.rate {
+        effects.push(Effect::Inhibits);
+    }
+    if !catalysed.products.keys().eq(blocked.products.keys()) {
+        effects.push(Effect::Redirects);
+    }
+    effects
+}
+
+/// The species a screen works on, as fresh particles and the spikes of their free sites
+struct Species<'a> {
+    config: &'a ReactorConfig,
+    atoms: Vec<AtomDef>,
+    spikes: Vec<Vec<SiteSpike>>,
+}
+
+impl<'a> Species<'a> {
+    fn new(config: &'a ReactorConfig) -> Species<'a> {
+        let mut species = Species {
+            config,
+            atoms: generate_species(config, &mut seeded(config.seed)),
+            spikes: vec![],
+        };
+        species.spikes = (0..species.atoms.len())
+            .map(|idx| free_spikes(&species.make(idx), 1))
+            .collect();
+        species
+    }
+
+    /// A fresh particle of species <idx>
+    fn make(&self, idx: usize) -> Particle {
+        let mut atom = self.atoms[idx]
+            .to_rbn()
+            .expect("generated atoms are always valid");
+        atom.set_site_policy(self.config.sites.clone());
+        make_atom(atom, self.config.temperature)
+    }
+
+    /// Outcome of <a> + <b> where the free sites of reactant <host> have the spikes <host_spikes>
+    fn outcome(&self, a: usize, b: usize, host: usize, host_spikes: &[SiteSpike]) -> Outcome {
+        let (spikes_a, spikes_b) = if host == 0 {
+            (host_spikes, &self.spikes[b][..])
+        } else {
+            (&self.spikes[a][..], host_spikes)
+        };
+        outcome(&|| self.make(a), spikes_a, &|| self.make(b), spikes_b)
+    }
+
+    /// Every catalysis of <a> + <b> by <c> bonded to reactant <host>
+    fn catalysis(&self, a: usize, b: usize, c: usize, host: usize) -> Vec<Catalysis> {
+        let host_idx = if host == 0 { a } else { b };
+        let mut found = Vec::new();
+        for (host_site, host_spike) in &self.spikes[host_idx] {
+            for (c_site, c_spike) in &self.spikes[c] {
+                match (host_spike, c_spike) {
+                    (Some(hs), Some(cs)) if spikes_bond(*hs, *cs) => {}
+                    _ => continue,
+                }
+                let held = match catalysed_spikes(
+                    self.make(host_idx),
+                    *host_site,
+                    self.make(c),
+                    *c_site,
+                    self.config.temperature,
+                ) {
+                    Some(held) => held,
+                    None => continue,
+                };
+                let taken: Vec<SiteSpike> = self.spikes[host_idx]
+                    .iter()
+                    .filter(|(site, _spike)| site != host_site)
+                    .cloned()
+                    .collect();
+                let blocked = self.outcome(a, b, host, &taken);
+                let with = self.outcome(a, b, host, &held);
+                let changes = effects(&blocked, &with);
+                if !changes.is_empty() {
+                    found.push(Catalysis {
+                        catalyst: c,
+                        host,
+                        sites: (host_site.1, c_site.1),
+                        blocked,
+                        outcome: with,
+                        effects: changes,
+                    });
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Screens every reaction A + B between the species of <config>, A no later than B, with every
+/// species as the catalyst bonded to either reactant at every pair of sites whose spikes can bond.
+/// Bonds that would not hold once their dynamics are coupled are skipped. Species come from
+/// <config.seed> as they do in Reactor::new
+pub fn screen(config: &ReactorConfig) -> CatalysisReport {
+    let species = Species::new(config);
+    let count = species.atoms.len();
+    let mut reactions = Vec::new();
+    for a in 0..count {
+        for b in a..count {
+            let alone = species.outcome(a, b, 0, &species.spikes[a]);
+            let mut catalysis = Vec::new();
+            for c in 0..count {
+                for host in 0..2 {
+                    catalysis.extend(species.catalysis(a, b, c, host));
+                }
+            }
+            reactions.push(Screen {
+                a,
+                b,
+                alone,
+                catalysis,
+            });
+        }
+    }
+    CatalysisReport {
+        species: (0..count)
+            .map(|idx| species_name(&species.make(idx)))
+            .collect(),
+        reactions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reactor::network::ReactionNetwork;
+    use crate::util::grouping::SitePolicy;
+
+    #[test]
+    fn screen_finds_catalysis() {
+        let config = ReactorConfig {
+            species: 3,
+            copies: 1,
+            n: 10,
+            k: 2,
+            bias: 0.5,
+            temperature: 0,
+            iterations: 0,
+            seed: 11,
+            break_probability: 0.0,
+            sites: SitePolicy::default(),
+            flow: None,
+            metropolis: false,
+        };
+        let report = screen(&config);
+        assert_eq!(report.species.len(), 3);
+        // A + B with A no later than B
+        assert_eq!(report.reactions.len(), 6);
+        let found: Vec<&Catalysis> = report
+            .reactions
+            .iter()
+            .flat_map(|r| r.catalysis.iter())
+            .collect();
+        assert!(!found.is_empty());
+        for cat in &found {
+            assert!(!cat.effects.is_empty());
+            assert_ne!(cat.outcome, cat.blocked);
+            assert!(cat.outcome.rate >= 0.0 && cat.outcome.rate <= 1.0);
+        }
+        assert_eq!(report, screen(&config));
+
+        let network = ReactionNetwork::from_report(&report);
+        let names: Vec<&String> = network.species.iter().map(|s| &s.name).collect();
+        for reaction in &network.reactions {
+            assert!(reaction
+                .reactants
+                .iter()
+                .chain(&reaction.products)
+                .all(|s| names.contains(&s)));
+        }
+        assert!(network.reactions.iter().any(|r| !r.catalysts.is_empty()));
+        let dot = network.to_dot();
+        assert!(dot.starts_with("digraph reactions {"));
+        assert!(dot.contains("style=dashed"));
+    }
+}
@@ -0,0 +1,63 @@
+//! Reaction energies. A bond's energy is minus the difference between the spikes of its two sites,
+//! see bond_energy, so forming a bond gives energy off and breaking one takes it up. The ledger
+//! keeps the account and the Metropolis test, when configured, turns reactions down with the
+//! Boltzmann probability of the energy they need at the reactor's temperature
+use crate::temp::Temperature;
+use rand::Rng;
+
+/// Thermal energy kT of <temperature> in spike units, one for every Node it starts on. A reactor
+/// at temperature 0 has none, and its reactions never take energy up
+pub fn kt(temperature: Temperature) -> f64 {
+    f64::from(temperature.count_ones())
+}
+
+/// Metropolis test at <temperature> for a reaction changing the energy of the particles by
+/// <delta>. Reactions that give energy off always happen without drawing from <rng>, the others
+/// with probability exp(-delta / kT), never at kT 0
+pub fn metropolis<R: Rng>(temperature: Temperature, delta: i64, rng: &mut R) -> bool {
+    if delta <= 0 {
+        return true;
+    }
+    let kt = kt(temperature);
+    let p = if kt > 0.0 {
+        (-(delta as f64) / kt).exp()
+    } else {
+        0.0
+    };
+    rng.gen_bool(p)
+}
+
+/// Energy that has flowed through a reactor, in spike units. Atoms have no energy, so the particles
+/// of a reactor that started from atoms always hold balance() between them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnergyLedger {
+    /// Energy given off by the bonds formed
+    pub released: i64,
+    /// Energy taken up by the bonds broken
+    pub absorbed: i64,
+    /// Energy of the particles that flowed out, at most 0
+    pub carried_out: i64,
+    /// Change in the energy of the bonds that held when products were calculated, their atoms'
+    /// spikes follow the dynamics of the whole product
+    #[serde(default)]
+    pub shifted: i64,
+    /// Reactions the Metropolis test turned down
+    pub rejected: u64,
+}
+
+impl EnergyLedger {
+    /// Energy the particles should hold between them
+    pub fn balance(&self) -> i64 {
+        self.absorbed - self.released - self.carried_out + self.shifted
+    }
+
+    /// Records a bond of energy <energy> forming
+    pub fn formed(&mut self, energy: i64) {
+        self.released -= energy;
+    }
+
+    /// Records a bond of energy <energy> breaking
+    pub fn broken(&mut self, energy: i64) {
+        self.absorbed -= energy;
+    }
+}
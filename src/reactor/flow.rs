@@ -0,0 +1,107 @@
+//! Flow reactor (chemostat) mode. Fresh atoms flow in at a set rate and random particles flow out
+//! whenever the population is over capacity, so reactions can carry on instead of the reactor
+//! running out of free sites
+use super::{make_atom, ReactorConfig};
+use crate::config::ConfigError;
+use crate::formats::json::AtomDef;
+use crate::particle::Particle;
+use crate::rbn::RBN;
+use rand::Rng;
+
+/// Where the atoms flowing in come from
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Feed {
+    /// Copies of the species the reactor started with, chosen uniformly
+    #[default]
+    Species,
+    /// New random atoms of the configured n, k and bias
+    Random,
+    /// Copies of these atoms, chosen uniformly
+    Atoms { atoms: Vec<AtomDef> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlowConfig {
+    /// Mean atoms flowing in each iteration, the whole part always and one more with the
+    /// fractional part as probability
+    pub inflow: f64,
+    /// Largest population, random particles flow out while there are more
+    pub capacity: usize,
+    #[serde(default)]
+    pub feed: Feed,
+}
+
+impl FlowConfig {
+    /// Checks the flow can be run
+    pub fn check(&self) -> Result<(), ConfigError> {
+        if !(self.inflow >= 0.0 && self.inflow.is_finite()) {
+            return Err(ConfigError::Parse(format!(
+                "inflow {} must be a rate of at least 0",
+                self.inflow
+            )));
+        }
+        if self.capacity == 0 {
+            return Err(ConfigError::Parse(
+                "capacity must be at least 1".to_string(),
+            ));
+        }
+        if let Feed::Atoms { atoms } = &self.feed {
+            if atoms.is_empty() {
+                return Err(ConfigError::Parse("the feed has no atoms".to_string()));
+            }
+            for atom in atoms {
+                let rbn = atom
+                    .to_rbn()
+                    .map_err(|err| ConfigError::Parse(err.to_string()))?;
+                if rbn.is_probabilistic() {
+                    return Err(ConfigError::Parse(
+                        "probabilistic atoms cannot be fed into a reactor".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Atoms flowing in for one iteration of <config>, <species> are the starting species
+pub(super) fn inflow<R: Rng>(
+    config: &ReactorConfig,
+    flow: &FlowConfig,
+    species: &[AtomDef],
+    rng: &mut R,
+) -> Vec<Particle> {
+    let mut count = flow.inflow.floor() as usize;
+    if rng.gen_bool(flow.inflow.fract()) {
+        count += 1;
+    }
+    (0..count)
+        .map(|_i| {
+            let def = match &flow.feed {
+                Feed::Species => species[rng.gen_range(0, species.len())].clone(),
+                Feed::Random => {
+                    AtomDef::from_rbn(&RBN::new_with_rng(config.k, config.n, config.bias, rng))
+                }
+                Feed::Atoms { atoms } => atoms[rng.gen_range(0, atoms.len())].clone(),
+            };
+            let mut atom = def
+                .to_rbn()
+                .expect("feed atoms are checked with the config");
+            if def.sites.is_none() {
+                atom.set_site_policy(config.sites.clone());
+            }
+            make_atom(atom, config.temperature)
+        })
+        .collect()
+}
+
+/// Index of a particle to flow out of <count> particles, None while the population fits
+pub(super) fn outflow<R: Rng>(flow: &FlowConfig, count: usize, rng: &mut R) -> Option<usize> {
+    if count > flow.capacity {
+        Some(rng.gen_range(0, count))
+    } else {
+        None
+    }
+}
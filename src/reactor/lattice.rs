@@ -0,0 +1,471 @@
+//! Spatial reactor, particles live in the cells of a 2D grid, hop between neighbouring cells and
+//! only react with particles in their own or a neighbouring cell. Bonding and bond breaking follow
+//! the same rules as the well-stirred reactor
+use super::{
+    accept, flow, generate_species, pick_bond, pick_reaction, populate, reaction_energy, rebuild,
+    settle, summarise, EnergyLedger, ReactorConfig, ReactorSummary,
+};
+use crate::config::ConfigError;
+use crate::formats::json::AtomDef;
+use crate::particle::{Particle, ParticleDef};
+use crate::util::rng::{seeded, SpikyRng};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::BTreeMap;
+
+fn default_diffusion() -> Vec<f64> {
+    vec![1.0]
+}
+
+fn default_periodic() -> bool {
+    true
+}
+
+/// Moves to the four neighbours of a cell, up, right, down and left
+const MOVES: [(isize, isize); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LatticeConfig {
+    /// Population and reaction rules, exactly as for the well-stirred reactor
+    pub reactor: ReactorConfig,
+    pub width: usize,
+    pub height: usize,
+    /// Probability of a particle hopping to a neighbouring cell each iteration, by size. Entry i
+    /// is for particles of i + 1 components, the last entry covers every larger size
+    #[serde(default = "default_diffusion")]
+    pub diffusion: Vec<f64>,
+    /// Particles leaving one edge come back on the opposite one, otherwise hops off the grid are
+    /// not made
+    #[serde(default = "default_periodic")]
+    pub periodic: bool,
+}
+
+impl LatticeConfig {
+    /// Checks the configuration describes a lattice that can be run
+    pub fn check(&self) -> Result<(), ConfigError> {
+        self.reactor.check()?;
+        if self.width == 0 || self.height == 0 {
+            return Err(ConfigError::Parse(
+                "width and height must be at least 1".to_string(),
+            ));
+        }
+        if self.diffusion.is_empty() {
+            return Err(ConfigError::Parse(
+                "diffusion needs a rate for at least one size".to_string(),
+            ));
+        }
+        if let Some(rate) = self.diffusion.iter().find(|r| !(0.0..=1.0).contains(*r)) {
+            return Err(ConfigError::Parse(format!(
+                "diffusion rate {} is not a probability",
+                rate
+            )));
+        }
+        Ok(())
+    }
+
+    /// Hop probability of a particle of <size> components
+    pub fn diffusion_rate(&self, size: usize) -> f64 {
+        self.diffusion[(size.max(1) - 1).min(self.diffusion.len() - 1)]
+    }
+}
+
+/// Particles in one cell
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CellSnapshot {
+    pub x: usize,
+    pub y: usize,
+    pub particles: usize,
+    /// Atoms in the cell, counting every component
+    pub atoms: usize,
+    /// Number of particles with each number of components
+    pub sizes: BTreeMap<usize, usize>,
+}
+
+/// The population of every occupied cell, row by row
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatticeSnapshot {
+    pub iteration: u64,
+    pub cells: Vec<CellSnapshot>,
+}
+
+/// Everything needed to carry on a run exactly where it stopped
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LatticeCheckpoint {
+    pub config: LatticeConfig,
+    pub iteration: u64,
+    pub bonds_formed: u64,
+    pub bonds_broken: u64,
+    #[serde(default)]
+    pub atoms_in: u64,
+    #[serde(default)]
+    pub particles_out: u64,
+    #[serde(default)]
+    pub energy_flow: EnergyLedger,
+    pub rng: SpikyRng,
+    pub particles: Vec<ParticleDef>,
+    /// Cell (x, y) of each particle
+    pub cells: Vec<(usize, usize)>,
+}
+
+pub struct Lattice {
+    pub config: LatticeConfig,
+    pub particles: Vec<Particle>,
+    /// Cell (x, y) of each particle, index i is particles[i]
+    pub cells: Vec<(usize, usize)>,
+    pub iteration: u64,
+    pub bonds_formed: u64,
+    pub bonds_broken: u64,
+    pub atoms_in: u64,
+    pub particles_out: u64,
+    pub energy_flow: EnergyLedger,
+    /// The species the lattice started with, what a species feed supplies
+    species: Vec<AtomDef>,
+    rng: SpikyRng,
+}
+
+impl Lattice {
+    /// Fills the reactor as Reactor::new does and scatters the particles over the grid, everything
+    /// is drawn from <config.reactor.seed>
+    pub fn new(config: LatticeConfig) -> Lattice {
+        let mut rng = seeded(config.reactor.seed);
+        let species = generate_species(&config.reactor, &mut rng);
+        let particles = populate(&config.reactor, &species);
+        let cells = particles
+            .iter()
+            .map(|_p| {
+                (
+                    rng.gen_range(0, config.width),
+                    rng.gen_range(0, config.height),
+                )
+            })
+            .collect();
+        Lattice {
+            config,
+            particles,
+            cells,
+            iteration: 0,
+            bonds_formed: 0,
+            bonds_broken: 0,
+            atoms_in: 0,
+            particles_out: 0,
+            energy_flow: EnergyLedger::default(),
+            species,
+            rng,
+        }
+    }
+
+    /// Picks a run up from <checkpoint>, the particles are rebuilt from their definitions
+    pub fn resume(checkpoint: LatticeCheckpoint) -> Result<Lattice, ConfigError> {
+        if checkpoint.cells.len() != checkpoint.particles.len() {
+            return Err(ConfigError::Parse(format!(
+                "{} cells for {} particles",
+                checkpoint.cells.len(),
+                checkpoint.particles.len()
+            )));
+        }
+        let mut particles = Vec::with_capacity(checkpoint.particles.len());
+        for def in &checkpoint.particles {
+            particles.push(rebuild(def, checkpoint.config.reactor.temperature)?);
+        }
+        let species = generate_species(
+            &checkpoint.config.reactor,
+            &mut seeded(checkpoint.config.reactor.seed),
+        );
+        Ok(Lattice {
+            species,
+            config: checkpoint.config,
+            particles,
+            cells: checkpoint.cells,
+            iteration: checkpoint.iteration,
+            bonds_formed: checkpoint.bonds_formed,
+            bonds_broken: checkpoint.bonds_broken,
+            atoms_in: checkpoint.atoms_in,
+            particles_out: checkpoint.particles_out,
+            energy_flow: checkpoint.energy_flow,
+            rng: checkpoint.rng,
+        })
+    }
+
+    pub fn checkpoint(&self) -> LatticeCheckpoint {
+        LatticeCheckpoint {
+            config: self.config.clone(),
+            iteration: self.iteration,
+            bonds_formed: self.bonds_formed,
+            bonds_broken: self.bonds_broken,
+            atoms_in: self.atoms_in,
+            particles_out: self.particles_out,
+            energy_flow: self.energy_flow,
+            rng: self.rng.clone(),
+            particles: self.particles.iter().map(|p| p.to_particle_def()).collect(),
+            cells: self.cells.clone(),
+        }
+    }
+
+    /// Runs the remaining iterations of the configuration
+    pub fn run(&mut self) {
+        while self.iteration < self.config.reactor.iterations {
+            self.step();
+        }
+    }
+
+    /// Runs the remaining iterations, calling <on_every> every <every> iterations and once more
+    /// at the end, for snapshots or checkpoints
+    pub fn run_with<F>(&mut self, every: u64, mut on_every: F) -> Result<(), ConfigError>
+    where
+        F: FnMut(&Lattice) -> Result<(), ConfigError>,
+    {
+        while self.iteration < self.config.reactor.iterations {
+            self.step();
+            if every > 0 && self.iteration.is_multiple_of(every) {
+                on_every(self)?;
+            }
+        }
+        on_every(self)
+    }
+
+    /// One iteration, every particle may hop, then a random particle collides with one in its own
+    /// or a neighbouring cell, followed by a chance of a random bond breaking and, for a flow
+    /// reactor, the inflow into random cells and the outflow
+    pub fn step(&mut self) {
+        self.iteration += 1;
+        self.diffuse();
+        self.collide();
+        if self.rng.gen_bool(self.config.reactor.break_probability) {
+            self.break_random_bond();
+        }
+        if let Some(flow) = &self.config.reactor.flow {
+            for atom in flow::inflow(&self.config.reactor, flow, &self.species, &mut self.rng) {
+                let cell = (
+                    self.rng.gen_range(0, self.config.width),
+                    self.rng.gen_range(0, self.config.height),
+                );
+                self.particles.push(atom);
+                self.cells.push(cell);
+                self.atoms_in += 1;
+            }
+            while let Some(idx) = flow::outflow(flow, self.particles.len(), &mut self.rng) {
+                self.cells.swap_remove(idx);
+                self.energy_flow.carried_out += self.particles.swap_remove(idx).energy();
+                self.particles_out += 1;
+            }
+        }
+    }
+
+    /// Cell one step from <cell> in direction <mv>, None if that is off a non periodic grid
+    fn neighbour(&self, cell: (usize, usize), mv: (isize, isize)) -> Option<(usize, usize)> {
+        let (w, h) = (self.config.width as isize, self.config.height as isize);
+        let (x, y) = (cell.0 as isize + mv.0, cell.1 as isize + mv.1);
+        if self.config.periodic {
+            Some((x.rem_euclid(w) as usize, y.rem_euclid(h) as usize))
+        } else if x < 0 || y < 0 || x >= w || y >= h {
+            None
+        } else {
+            Some((x as usize, y as usize))
+        }
+    }
+
+    /// <cell> and the cells next to it
+    fn neighbourhood(&self, cell: (usize, usize)) -> Vec<(usize, usize)> {
+        let mut cells = vec![cell];
+        for mv in &MOVES {
+            if let Some(next) = self.neighbour(cell, *mv) {
+                if !cells.contains(&next) {
+                    cells.push(next);
+                }
+            }
+        }
+        cells
+    }
+
+    fn diffuse(&mut self) {
+        for idx in 0..self.particles.len() {
+            let rate = self
+                .config
+                .diffusion_rate(self.particles[idx].components.len());
+            if self.rng.gen_bool(rate) {
+                let mv = MOVES[self.rng.gen_range(0, MOVES.len())];
+                if let Some(next) = self.neighbour(self.cells[idx], mv) {
+                    self.cells[idx] = next;
+                }
+            }
+        }
+    }
+
+    fn collide(&mut self) {
+        if self.particles.len() < 2 {
+            return;
+        }
+        let a = self.rng.gen_range(0, self.particles.len());
+        let near = self.neighbourhood(self.cells[a]);
+        let partners: Vec<usize> = (0..self.particles.len())
+            .filter(|idx| *idx != a && near.contains(&self.cells[*idx]))
+            .collect();
+        let b = match partners.choose(&mut self.rng) {
+            Some(b) => *b,
+            None => return,
+        };
+        let (site_a, site_b) =
+            match pick_reaction(&self.particles[a], &self.particles[b], &mut self.rng) {
+                Some(sites) => sites,
+                None => return,
+            };
+        let energy = reaction_energy(&self.particles[a], site_a, &self.particles[b], site_b);
+        let config = &self.config.reactor;
+        if !accept(config, energy, &mut self.rng, &mut self.energy_flow) {
+            return;
+        }
+        let cell = self.cells[a];
+        // remove the later index first so the earlier one stays valid
+        let (first, second) = if a > b { (a, b) } else { (b, a) };
+        let p_first = self.remove(first);
+        let p_second = self.remove(second);
+        let (p_a, p_b) = if first == a {
+            (p_first, p_second)
+        } else {
+            (p_second, p_first)
+        };
+        let product = p_a.bond_with(site_a, p_b, site_b);
+        self.bonds_formed += 1;
+        self.energy_flow.formed(energy);
+        let temperature = self.config.reactor.temperature;
+        let (particles, broken) = settle(vec![product], temperature, &mut self.energy_flow);
+        for particle in particles {
+            self.particles.push(particle);
+            self.cells.push(cell);
+        }
+        self.bonds_broken += broken;
+    }
+
+    /// Breaks a bond chosen uniformly from every bond on the grid, the pieces stay in the cell
+    fn break_random_bond(&mut self) {
+        if let Some((idx, bond)) = pick_bond(&self.particles, &mut self.rng) {
+            let energy = self.particles[idx].get_bond_energy(bond);
+            let config = &self.config.reactor;
+            if !accept(config, -energy, &mut self.rng, &mut self.energy_flow) {
+                return;
+            }
+            let cell = self.cells[idx];
+            let particle = self.remove(idx);
+            self.bonds_broken += 1;
+            self.energy_flow.broken(energy);
+            let temperature = self.config.reactor.temperature;
+            let (pieces, broken) = settle(
+                particle.break_bond(bond),
+                temperature,
+                &mut self.energy_flow,
+            );
+            for piece in pieces {
+                self.particles.push(piece);
+                self.cells.push(cell);
+            }
+            self.bonds_broken += broken;
+        }
+    }
+
+    fn remove(&mut self, idx: usize) -> Particle {
+        self.cells.swap_remove(idx);
+        self.particles.swap_remove(idx)
+    }
+
+    /// Population of every occupied cell
+    pub fn snapshot(&self) -> LatticeSnapshot {
+        let mut cells = BTreeMap::<(usize, usize), CellSnapshot>::new();
+        for (particle, (x, y)) in self.particles.iter().zip(&self.cells) {
+            let cell = cells.entry((*y, *x)).or_insert_with(|| CellSnapshot {
+                x: *x,
+                y: *y,
+                particles: 0,
+                atoms: 0,
+                sizes: BTreeMap::new(),
+            });
+            let size = particle.components.len();
+            cell.particles += 1;
+            cell.atoms += size;
+            *cell.sizes.entry(size).or_insert(0) += 1;
+        }
+        LatticeSnapshot {
+            iteration: self.iteration,
+            cells: cells.into_values().collect(),
+        }
+    }
+
+    pub fn summary(&self) -> ReactorSummary {
+        summarise(
+            &self.particles,
+            self.iteration,
+            (self.bonds_formed, self.bonds_broken),
+            (self.atoms_in, self.particles_out),
+            self.energy_flow,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    fn config() -> LatticeConfig {
+        config::from_toml(
+            "width = 4\nheight = 3\ndiffusion = [0.5, 0.1]\n\
+             [reactor]\nspecies = 3\ncopies = 5\nn = 12\nk = 2\niterations = 300\nseed = 5\n\
+             break_probability = 0.1\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn lattice_is_reproducible_and_conserves_atoms() {
+        let mut first = Lattice::new(config());
+        first.run();
+        let mut second = Lattice::new(config());
+        second.run();
+        assert_eq!(first.summary(), second.summary());
+        assert_eq!(first.snapshot(), second.snapshot());
+        let snap = first.snapshot();
+        assert_eq!(snap.cells.iter().map(|c| c.atoms).sum::<usize>(), 15);
+        assert!(snap.cells.iter().all(|c| c.x < 4 && c.y < 3));
+    }
+
+    #[test]
+    fn bonds_only_form_between_neighbours() {
+        // two particles that never move, far apart and then side by side
+        let mut still = config();
+        still.periodic = false;
+        still.diffusion = vec![0.0];
+        still.reactor.species = 1;
+        still.reactor.copies = 2;
+        still.reactor.break_probability = 0.0;
+        let mut apart = Lattice::new(still.clone());
+        apart.cells = vec![(0, 0), (3, 2)];
+        apart.run();
+        assert_eq!(apart.bonds_formed, 0);
+        assert_eq!(apart.cells, vec![(0, 0), (3, 2)]);
+        assert_eq!(apart.neighbourhood((0, 0)).len(), 3);
+
+        // copies of one atom bond if some site has a nonzero spike, which this seed gives
+        let mut close = Lattice::new(still);
+        close.cells = vec![(3, 2), (3, 1)];
+        close.run();
+        assert_eq!(close.bonds_formed, 1);
+        // the bonded particle is where the particle that started the collision was
+        assert_eq!(close.cells.len(), 1);
+        assert!([(3, 2), (3, 1)].contains(&close.cells[0]));
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let mut whole = Lattice::new(config());
+        whole.run();
+        let mut halted = Lattice::new(config());
+        while halted.iteration < 120 {
+            halted.step();
+        }
+        let json = serde_json::to_string(&halted.checkpoint()).unwrap();
+        let mut resumed = Lattice::resume(serde_json::from_str(&json).unwrap()).unwrap();
+        resumed.run();
+        assert_eq!(resumed.summary(), whole.summary());
+        assert_eq!(resumed.snapshot(), whole.snapshot());
+    }
+}
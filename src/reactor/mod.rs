@@ -0,0 +1,619 @@
+//! Well-stirred reactor, a population of particles in which any two can meet and react. Two
+//! particles meet at a random free bonding site each and bond if the spikes of the sites have
+//! opposite signs, and the product is calculated as a whole, bonds that no longer hold breaking.
+//! Bonds also break at random. The energy of every reaction is accounted for and may decide
+//! whether it happens, see energy
+use crate::config::{check_atom_params, ConfigError};
+use crate::formats::json::AtomDef;
+use crate::particle::{Component, Particle, ParticleDef};
+use crate::rbn::RBN;
+use crate::temp::Temperature;
+use crate::util::bonding::{bond_energy, spikes_bond, IsSubSymbolic};
+use crate::util::grouping::SitePolicy;
+use crate::util::recorder::NullRecorder;
+use crate::util::rng::{seeded, SpikyRng};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::BTreeMap;
+
+pub mod catalysis;
+pub mod energy;
+pub mod flow;
+pub mod lattice;
+pub mod network;
+pub mod stats;
+
+use self::energy::EnergyLedger;
+use self::flow::{Feed, FlowConfig};
+
+fn default_bias() -> f64 {
+    0.5
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReactorConfig {
+    /// Number of distinct random atoms
+    pub species: usize,
+    /// Copies of each species in the initial population
+    pub copies: usize,
+    /// Nodes per atom
+    pub n: u16,
+    /// Inputs per Node
+    pub k: u8,
+    /// Probability of each truth table entry being true
+    #[serde(default = "default_bias")]
+    pub bias: f64,
+    /// Initial state every atom is calculated from
+    #[serde(default)]
+    pub temperature: Temperature,
+    pub iterations: u64,
+    pub seed: u64,
+    /// Probability that a random bond in the population breaks in each iteration
+    #[serde(default)]
+    pub break_probability: f64,
+    /// How each atom's bonding sites are generated
+    #[serde(default)]
+    pub sites: SitePolicy,
+    /// Inflow and outflow, a closed reactor if not given
+    #[serde(default)]
+    pub flow: Option<FlowConfig>,
+    /// Metropolis test on the energy of each reaction at the temperature, see energy::kt. Every
+    /// reaction that can happen does if false
+    #[serde(default)]
+    pub metropolis: bool,
+}
+
+impl ReactorConfig {
+    /// Checks the configuration describes a reactor that can be run
+    pub fn check(&self) -> Result<(), ConfigError> {
+        check_atom_params(self.n, self.k, self.bias)?;
+        self.sites.check(self.n as usize)?;
+        if let Some(flow) = &self.flow {
+            flow.check()?;
+            if flow.feed == Feed::Species && self.species == 0 {
+                return Err(ConfigError::Parse(
+                    "a species feed needs at least one species".to_string(),
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.break_probability) {
+            return Err(ConfigError::Parse(format!(
+                "break_probability {} is not a probability",
+                self.break_probability
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// State of the reactor at the end of a run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReactorSummary {
+    pub iterations: u64,
+    pub particles: usize,
+    pub bonds_formed: u64,
+    pub bonds_broken: u64,
+    /// Atoms that flowed in and particles that flowed out, zero for a closed reactor
+    pub atoms_in: u64,
+    pub particles_out: u64,
+    /// Energy of the particles between them, always energy_flow.balance()
+    pub energy: i64,
+    pub energy_flow: EnergyLedger,
+    /// Number of particles with each number of components
+    pub sizes: BTreeMap<usize, usize>,
+}
+
+/// Everything needed to carry on a run exactly where it stopped
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReactorCheckpoint {
+    pub config: ReactorConfig,
+    pub iteration: u64,
+    pub bonds_formed: u64,
+    pub bonds_broken: u64,
+    #[serde(default)]
+    pub atoms_in: u64,
+    #[serde(default)]
+    pub particles_out: u64,
+    #[serde(default)]
+    pub energy_flow: EnergyLedger,
+    pub rng: SpikyRng,
+    pub particles: Vec<ParticleDef>,
+}
+
+pub struct Reactor {
+    pub config: ReactorConfig,
+    pub particles: Vec<Particle>,
+    /// Iterations run so far
+    pub iteration: u64,
+    pub bonds_formed: u64,
+    pub bonds_broken: u64,
+    pub atoms_in: u64,
+    pub particles_out: u64,
+    pub energy_flow: EnergyLedger,
+    /// The species the reactor started with, what a species feed supplies
+    species: Vec<AtomDef>,
+    rng: SpikyRng,
+}
+
+impl Reactor {
+    /// Generates <config.species> random atoms and fills the reactor with <config.copies> of each,
+    /// everything is drawn from <config.seed>
+    pub fn new(config: ReactorConfig) -> Reactor {
+        let mut rng = seeded(config.seed);
+        let species = generate_species(&config, &mut rng);
+        let particles = populate(&config, &species);
+        Reactor {
+            config,
+            particles,
+            iteration: 0,
+            bonds_formed: 0,
+            bonds_broken: 0,
+            atoms_in: 0,
+            particles_out: 0,
+            energy_flow: EnergyLedger::default(),
+            species,
+            rng,
+        }
+    }
+
+    /// Picks a run up from <checkpoint>, the particles are rebuilt from their definitions
+    pub fn resume(checkpoint: ReactorCheckpoint) -> Result<Reactor, ConfigError> {
+        let mut particles = Vec::with_capacity(checkpoint.particles.len());
+        for def in &checkpoint.particles {
+            particles.push(rebuild(def, checkpoint.config.temperature)?);
+        }
+        Ok(Reactor {
+            species: generate_species(&checkpoint.config, &mut seeded(checkpoint.config.seed)),
+            config: checkpoint.config,
+            particles,
+            iteration: checkpoint.iteration,
+            bonds_formed: checkpoint.bonds_formed,
+            bonds_broken: checkpoint.bonds_broken,
+            atoms_in: checkpoint.atoms_in,
+            particles_out: checkpoint.particles_out,
+            energy_flow: checkpoint.energy_flow,
+            rng: checkpoint.rng,
+        })
+    }
+
+    pub fn checkpoint(&self) -> ReactorCheckpoint {
+        ReactorCheckpoint {
+            config: self.config.clone(),
+            iteration: self.iteration,
+            bonds_formed: self.bonds_formed,
+            bonds_broken: self.bonds_broken,
+            atoms_in: self.atoms_in,
+            particles_out: self.particles_out,
+            energy_flow: self.energy_flow,
+            rng: self.rng.clone(),
+            particles: self.particles.iter().map(|p| p.to_particle_def()).collect(),
+        }
+    }
+
+    /// Runs the remaining iterations of the configuration
+    pub fn run(&mut self) {
+        while self.iteration < self.config.iterations {
+            self.step();
+        }
+    }
+
+    /// Runs the remaining iterations, calling <on_checkpoint> every <every> iterations and once
+    /// more at the end
+    pub fn run_checkpointed<F>(
+        &mut self,
+        every: u64,
+        mut on_checkpoint: F,
+    ) -> Result<(), ConfigError>
+    where
+        F: FnMut(&Reactor) -> Result<(), ConfigError>,
+    {
+        while self.iteration < self.config.iterations {
+            self.step();
+            if every > 0 && self.iteration.is_multiple_of(every) {
+                on_checkpoint(self)?;
+            }
+        }
+        on_checkpoint(self)
+    }
+
+    /// One iteration, a collision between two random particles followed by a chance of a random
+    /// bond breaking and, for a flow reactor, the inflow and outflow
+    pub fn step(&mut self) {
+        self.iteration += 1;
+        self.collide();
+        if self.rng.gen_bool(self.config.break_probability) {
+            self.break_random_bond();
+        }
+        if let Some(flow) = &self.config.flow {
+            let fresh = flow::inflow(&self.config, flow, &self.species, &mut self.rng);
+            self.atoms_in += fresh.len() as u64;
+            self.particles.extend(fresh);
+            while let Some(idx) = flow::outflow(flow, self.particles.len(), &mut self.rng) {
+                self.energy_flow.carried_out += self.particles.swap_remove(idx).energy();
+                self.particles_out += 1;
+            }
+        }
+    }
+
+    fn collide(&mut self) {
+        let count = self.particles.len();
+        if count < 2 {
+            return;
+        }
+        let a = self.rng.gen_range(0, count);
+        let mut b = self.rng.gen_range(0, count - 1);
+        if b >= a {
+            b += 1;
+        }
+        let (site_a, site_b) =
+            match pick_reaction(&self.particles[a], &self.particles[b], &mut self.rng) {
+                Some(sites) => sites,
+                None => return,
+            };
+        let energy = reaction_energy(&self.particles[a], site_a, &self.particles[b], site_b);
+        if !accept(&self.config, energy, &mut self.rng, &mut self.energy_flow) {
+            return;
+        }
+        // remove the later index first so the earlier one stays valid
+        let (first, second) = if a > b { (a, b) } else { (b, a) };
+        let p_first = self.particles.swap_remove(first);
+        let p_second = self.particles.swap_remove(second);
+        let (p_a, p_b) = if first == a {
+            (p_first, p_second)
+        } else {
+            (p_second, p_first)
+        };
+        let product = p_a.bond_with(site_a, p_b, site_b);
+        self.bonds_formed += 1;
+        self.energy_flow.formed(energy);
+        let (particles, broken) = settle(
+            vec![product],
+            self.config.temperature,
+            &mut self.energy_flow,
+        );
+        self.particles.extend(particles);
+        self.bonds_broken += broken;
+    }
+
+    /// Breaks a bond chosen uniformly from every bond in the population
+    fn break_random_bond(&mut self) {
+        if let Some((idx, bond)) = pick_bond(&self.particles, &mut self.rng) {
+            let energy = self.particles[idx].get_bond_energy(bond);
+            if !accept(&self.config, -energy, &mut self.rng, &mut self.energy_flow) {
+                return;
+            }
+            let particle = self.particles.swap_remove(idx);
+            self.bonds_broken += 1;
+            self.energy_flow.broken(energy);
+            let (particles, broken) = settle(
+                particle.break_bond(bond),
+                self.config.temperature,
+                &mut self.energy_flow,
+            );
+            self.particles.extend(particles);
+            self.bonds_broken += broken;
+        }
+    }
+
+    /// Number of particles with each number of components
+    pub fn size_distribution(&self) -> BTreeMap<usize, usize> {
+        size_distribution(&self.particles)
+    }
+
+    pub fn summary(&self) -> ReactorSummary {
+        summarise(
+            &self.particles,
+            self.iteration,
+            (self.bonds_formed, self.bonds_broken),
+            (self.atoms_in, self.particles_out),
+            self.energy_flow,
+        )
+    }
+}
+
+/// Number of <particles> with each number of components
+pub fn size_distribution(particles: &[Particle]) -> BTreeMap<usize, usize> {
+    let mut sizes = BTreeMap::new();
+    for particle in particles {
+        *sizes.entry(particle.components.len()).or_insert(0) += 1;
+    }
+    sizes
+}
+
+/// Summary of a run at <iteration> with population <particles>, the bonds formed and broken, the
+/// atoms in and particles out and the energy that has flowed
+fn summarise(
+    particles: &[Particle],
+    iteration: u64,
+    (bonds_formed, bonds_broken): (u64, u64),
+    (atoms_in, particles_out): (u64, u64),
+    energy_flow: EnergyLedger,
+) -> ReactorSummary {
+    ReactorSummary {
+        iterations: iteration,
+        particles: particles.len(),
+        bonds_formed,
+        bonds_broken,
+        atoms_in,
+        particles_out,
+        energy: particles.iter().map(|p| p.energy()).sum(),
+        energy_flow,
+        sizes: size_distribution(particles),
+    }
+}
+
+/// Generates the <config.species> random atoms a reactor starts with from <rng>
+fn generate_species<R: Rng>(config: &ReactorConfig, rng: &mut R) -> Vec<AtomDef> {
+    (0..config.species)
+        .map(|_s| AtomDef::from_rbn(&RBN::new_with_rng(config.k, config.n, config.bias, rng)))
+        .collect()
+}
+
+/// <config.copies> particles of each of <species>
+fn populate(config: &ReactorConfig, species: &[AtomDef]) -> Vec<Particle> {
+    let mut particles = Vec::with_capacity(species.len() * config.copies);
+    for def in species {
+        for _c in 0..config.copies {
+            let mut atom = def.to_rbn().expect("generated atoms are always valid");
+            atom.set_site_policy(config.sites.clone());
+            particles.push(make_atom(atom, config.temperature));
+        }
+    }
+    particles
+}
+
+/// Picks a random free site on each of <a> and <b>, returning them if the spikes have opposite
+/// signs so the particles bond
+fn pick_reaction<R: Rng>(
+    a: &Particle,
+    b: &Particle,
+    rng: &mut R,
+) -> Option<((usize, usize), (usize, usize))> {
+    let site_a = *a.get_free_sites().choose(rng)?;
+    let site_b = *b.get_free_sites().choose(rng)?;
+    let spike_a = a.get_site_prop(site_a.0, site_a.1);
+    let spike_b = b.get_site_prop(site_b.0, site_b.1);
+    match (spike_a, spike_b) {
+        (Some(sa), Some(sb)) if spikes_bond(sa, sb) => Some((site_a, site_b)),
+        _ => None,
+    }
+}
+
+/// Energy of the bond <site_a> of <a> and <site_b> of <b> would form, both (component, site)
+fn reaction_energy(
+    a: &Particle,
+    site_a: (usize, usize),
+    b: &Particle,
+    site_b: (usize, usize),
+) -> i64 {
+    bond_energy(
+        a.get_site_prop(site_a.0, site_a.1).unwrap_or(0),
+        b.get_site_prop(site_b.0, site_b.1).unwrap_or(0),
+    )
+}
+
+/// Calculates each of <particles> from <temperature>, as a whole. While one is Unstable the first
+/// bond between its components that no longer holds is broken and the pieces are calculated again.
+/// Gives the Stable particles and the number of bonds broken, every energy counted in <ledger>
+fn settle(
+    mut unsettled: Vec<Particle>,
+    temperature: Temperature,
+    ledger: &mut EnergyLedger,
+) -> (Vec<Particle>, u64) {
+    let mut expected: i64 = unsettled.iter().map(|p| p.energy()).sum();
+    let mut settled = Vec::with_capacity(unsettled.len());
+    let mut broken = 0;
+    unsettled.reverse();
+    while let Some(mut particle) = unsettled.pop() {
+        let stability = particle.calculate_particle(temperature, &mut NullRecorder);
+        // a failing bond inside a component is not the reactor's to break
+        match particle.failing_bonds().first() {
+            Some(bond) if !stability.is_stable() => {
+                let energy = particle.get_bond_energy(*bond);
+                ledger.broken(energy);
+                expected -= energy;
+                broken += 1;
+                let mut pieces = particle.break_bond(*bond);
+                pieces.reverse();
+                unsettled.extend(pieces);
+            }
+            _ => settled.push(particle),
+        }
+    }
+    ledger.shifted += settled.iter().map(|p| p.energy()).sum::<i64>() - expected;
+    (settled, broken)
+}
+
+/// Rebuilds a particle of a checkpoint from <def>, calculated as a whole from <temperature> as it
+/// was in the run
+fn rebuild(def: &ParticleDef, temperature: Temperature) -> Result<Particle, ConfigError> {
+    let mut particle = def
+        .to_particle(temperature)
+        .map_err(|err| ConfigError::Parse(err.to_string()))?;
+    particle.calculate_particle(temperature, &mut NullRecorder);
+    Ok(particle)
+}
+
+/// Metropolis test of <config> for a reaction changing the energy by <delta>, counting it in
+/// <ledger> if it is turned down. Always true unless <config.metropolis>
+fn accept<R: Rng>(
+    config: &ReactorConfig,
+    delta: i64,
+    rng: &mut R,
+    ledger: &mut EnergyLedger,
+) -> bool {
+    if config.metropolis && !energy::metropolis(config.temperature, delta, rng) {
+        ledger.rejected += 1;
+        return false;
+    }
+    true
+}
+
+/// A bond chosen uniformly from every bond in <particles>, as the index of the particle and of the
+/// bond within it
+fn pick_bond<R: Rng>(particles: &[Particle], rng: &mut R) -> Option<(usize, usize)> {
+    let total: usize = particles.iter().map(|p| p.bonds.len()).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut pick = rng.gen_range(0, total);
+    let idx = particles
+        .iter()
+        .position(|p| {
+            if pick < p.bonds.len() {
+                true
+            } else {
+                pick -= p.bonds.len();
+                false
+            }
+        })
+        .unwrap();
+    Some((idx, pick))
+}
+
+/// Calculates <atom> from <temperature> and wraps it in a particle of its own
+fn make_atom(mut atom: RBN, temperature: Temperature) -> Particle {
+    atom.calculate_particle(temperature, &mut NullRecorder);
+    let comp: Box<dyn Component> = Box::new(atom);
+    Particle::new(vec![comp])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ReactorConfig {
+        ReactorConfig {
+            species: 3,
+            copies: 4,
+            n: 12,
+            k: 2,
+            bias: 0.5,
+            temperature: 0,
+            iterations: 200,
+            seed: 7,
+            break_probability: 0.1,
+            sites: SitePolicy::default(),
+            flow: None,
+            metropolis: false,
+        }
+    }
+
+    #[test]
+    fn reactor_is_reproducible() {
+        let mut first = Reactor::new(config());
+        first.run();
+        let mut second = Reactor::new(config());
+        second.run();
+        assert_eq!(first.summary(), second.summary());
+        // reactions conserve atoms
+        let atoms: usize = first.particles.iter().map(|p| p.components.len()).sum();
+        assert_eq!(atoms, 12);
+        assert_eq!(
+            first.bonds_formed - first.bonds_broken,
+            first
+                .particles
+                .iter()
+                .map(|p| p.bonds.len() as u64)
+                .sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let mut whole = Reactor::new(config());
+        whole.run();
+
+        let mut halted = Reactor::new(config());
+        let mut saved = None;
+        halted
+            .run_checkpointed(80, |r| {
+                if r.iteration == 80 {
+                    saved = Some(serde_json::to_string(&r.checkpoint()).unwrap());
+                }
+                Ok(())
+            })
+            .unwrap();
+        let checkpoint = serde_json::from_str(&saved.unwrap()).unwrap();
+        let mut resumed = Reactor::resume(checkpoint).unwrap();
+        assert_eq!(resumed.iteration, 80);
+        resumed.run();
+        assert_eq!(resumed.summary(), whole.summary());
+        let defs = |r: &Reactor| -> Vec<ParticleDef> {
+            r.particles.iter().map(|p| p.to_particle_def()).collect()
+        };
+        assert_eq!(defs(&resumed), defs(&whole));
+    }
+
+    #[test]
+    fn flow_reactor_stays_bounded() {
+        let mut cfg = config();
+        cfg.flow = Some(FlowConfig {
+            inflow: 0.5,
+            capacity: 10,
+            feed: Feed::Random,
+        });
+        assert!(cfg.check().is_ok());
+        let mut reactor = Reactor::new(cfg.clone());
+        let mut largest = 0;
+        while reactor.iteration < cfg.iterations {
+            reactor.step();
+            largest = largest.max(reactor.particles.len());
+        }
+        assert!(largest <= 10);
+        assert!(reactor.atoms_in > 50 && reactor.particles_out > 0);
+        let summary = reactor.summary();
+        assert_eq!(summary.energy, summary.energy_flow.balance());
+        assert!(summary.energy_flow.carried_out < 0);
+        let mut again = Reactor::new(cfg.clone());
+        again.run();
+        assert_eq!(again.summary(), reactor.summary());
+
+        // halfway checkpoints carry the flow on exactly
+        let mut halted = Reactor::new(cfg);
+        while halted.iteration < 100 {
+            halted.step();
+        }
+        let json = serde_json::to_string(&halted.checkpoint()).unwrap();
+        let mut resumed = Reactor::resume(serde_json::from_str(&json).unwrap()).unwrap();
+        resumed.run();
+        assert_eq!(resumed.summary(), reactor.summary());
+    }
+
+    #[test]
+    fn energy_accounting() {
+        let mut free = Reactor::new(config());
+        free.run();
+        let summary = free.summary();
+        assert_eq!(summary.energy, summary.energy_flow.balance());
+        assert!(summary.energy_flow.released > 0);
+        assert!(summary.energy_flow.absorbed > 0);
+        assert_eq!(summary.energy_flow.rejected, 0);
+
+        // at temperature 0 there is no thermal energy, bonds form and only break when a product
+        // is Unstable
+        let mut cold = config();
+        cold.break_probability = 1.0;
+        cold.metropolis = true;
+        assert_eq!(energy::kt(cold.temperature), 0.0);
+        assert!(cold.check().is_ok());
+        let mut cold = Reactor::new(cold);
+        cold.run();
+        let summary = cold.summary();
+        assert!(summary.bonds_formed > summary.bonds_broken);
+        // breaking a bond takes energy, so every bond broken was one that stopped holding
+        assert!(summary.bonds_broken > 0);
+        assert!(cold.particles.iter().all(|p| p.failing_bonds().is_empty()));
+        assert!(summary.energy_flow.rejected > 0);
+        assert_eq!(summary.energy, summary.energy_flow.balance());
+
+        // one unit of thermal energy for every Node the temperature starts on
+        assert_eq!(energy::kt(0b1011), 3.0);
+        let mut rng = seeded(1);
+        let taken = (0..1000)
+            .filter(|_t| energy::metropolis(0b1011, 3, &mut rng))
+            .count();
+        assert!(taken > 300 && taken < 440, "{} of 1000 near exp(-1)", taken);
+    }
+}
@@ -0,0 +1,152 @@
+//! Reaction network export. The species and reactions found by the catalysis screen, each
+//! reaction with its products and the catalysts that change it, as JSON or as a Graphviz DOT graph
+//! with a node per species and per reaction
+use super::catalysis::{CatalysisReport, Effect};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NetworkSpecies {
+    pub name: String,
+    /// Atoms in a particle of the species
+    pub components: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NetworkCatalyst {
+    pub species: String,
+    /// Every effect the catalyst has through any of the ways it can bond
+    pub effects: Vec<Effect>,
+    /// Highest rate of the reaction with the catalyst in place
+    pub rate: f64,
+    /// Products the reaction makes with the catalyst that it does not make alone
+    pub products: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NetworkReaction {
+    pub reactants: Vec<String>,
+    pub products: Vec<String>,
+    /// Rate of the reaction alone, see catalysis::Outcome
+    pub rate: f64,
+    pub catalysts: Vec<NetworkCatalyst>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReactionNetwork {
+    pub species: Vec<NetworkSpecies>,
+    pub reactions: Vec<NetworkReaction>,
+}
+
+impl ReactionNetwork {
+    /// The reactions of <report> that happen alone or with a catalyst, products and catalysts
+    /// are in name order
+    pub fn from_report(report: &CatalysisReport) -> ReactionNetwork {
+        let mut species: BTreeMap<String, usize> =
+            report.species.iter().map(|s| (s.clone(), 1)).collect();
+        let mut reactions = Vec::new();
+        for screen in &report.reactions {
+            let reactants = vec![
+                report.species[screen.a].clone(),
+                report.species[screen.b].clone(),
+            ];
+            let size = species[&reactants[0]] + species[&reactants[1]];
+            let mut catalysts: BTreeMap<String, NetworkCatalyst> = BTreeMap::new();
+            for cat in &screen.catalysis {
+                let name = &report.species[cat.catalyst];
+                let entry = catalysts
+                    .entry(name.clone())
+                    .or_insert_with(|| NetworkCatalyst {
+                        species: name.clone(),
+                        effects: vec![],
+                        rate: 0.0,
+                        products: vec![],
+                    });
+                for effect in &cat.effects {
+                    if !entry.effects.contains(effect) {
+                        entry.effects.push(*effect);
+                    }
+                }
+                entry.rate = entry.rate.max(cat.outcome.rate);
+                for product in cat.outcome.products.keys() {
+                    if !screen.alone.products.contains_key(product)
+                        && !entry.products.contains(product)
+                    {
+                        entry.products.push(product.clone());
+                    }
+                }
+            }
+            if screen.alone.rate == 0.0 && catalysts.is_empty() {
+                continue;
+            }
+            let mut catalysts: Vec<NetworkCatalyst> = catalysts.into_values().collect();
+            for cat in &mut catalysts {
+                cat.products.sort();
+                for product in &cat.products {
+                    species.insert(product.clone(), size);
+                }
+            }
+            let products: Vec<String> = screen.alone.products.keys().cloned().collect();
+            for product in &products {
+                species.insert(product.clone(), size);
+            }
+            reactions.push(NetworkReaction {
+                reactants,
+                products,
+                rate: screen.alone.rate,
+                catalysts,
+            });
+        }
+        ReactionNetwork {
+            species: species
+                .into_iter()
+                .map(|(name, components)| NetworkSpecies { name, components })
+                .collect(),
+            reactions,
+        }
+    }
+
+    /// Species are ellipses labelled with their name and size, reactions are boxes with their
+    /// rate. Reactants and products are joined to their reaction by solid edges, catalysts by
+    /// dashed edges labelled with their effects, and products made only with a catalyst by
+    /// dotted edges
+    pub fn to_dot(&self) -> String {
+        let mut form_string = String::new();
+        form_string.push_str("digraph reactions {\n");
+        for sp in &self.species {
+            form_string.push_str(&format!(
+                "\"{}\" [shape=ellipse, label=\"{}\\n{} atoms\"];\n",
+                sp.name, sp.name, sp.components
+            ));
+        }
+        for (idx, reaction) in self.reactions.iter().enumerate() {
+            form_string.push_str(&format!(
+                "r{} [shape=box, label=\"{:.3}\"];\n",
+                idx, reaction.rate
+            ));
+            for reactant in &reaction.reactants {
+                form_string.push_str(&format!("\"{}\" -> r{};\n", reactant, idx));
+            }
+            for product in &reaction.products {
+                form_string.push_str(&format!("r{} -> \"{}\";\n", idx, product));
+            }
+            for cat in &reaction.catalysts {
+                let effects: Vec<String> = cat
+                    .effects
+                    .iter()
+                    .map(|e| format!("{:?}", e).to_lowercase())
+                    .collect();
+                form_string.push_str(&format!(
+                    "\"{}\" -> r{} [style=dashed, label=\"{}\"];\n",
+                    cat.species,
+                    idx,
+                    effects.join(", ")
+                ));
+                for product in &cat.products {
+                    form_string.push_str(&format!("r{} -> \"{}\" [style=dotted];\n", idx, product));
+                }
+            }
+        }
+        form_string.push_str("}\n");
+        form_string
+    }
+}
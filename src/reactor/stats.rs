@@ -0,0 +1,348 @@
+//! Population statistics sampled during a reactor run, written as CSV tables. A species is every
+//! particle with the same definition, the same atoms bonded the same way, and is named by a hash
+//! of that definition so names agree between runs and across a resume. The definition is put in a
+//! canonical order first, so the name does not depend on the order the atoms bonded in
+use super::size_distribution;
+use crate::particle::{ComponentDef, Particle, ParticleDef};
+use crate::util::bonding::Bond;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+
+/// Columns of the time series, one row per sample
+pub const STATS_HEADER: &str =
+    "iteration,particles,atoms,free_sites,bonds_formed,bonds_broken,species,shannon";
+/// Columns of the species counts, one row per species per sample
+pub const SPECIES_HEADER: &str = "iteration,species,components,count";
+/// Columns of the size distribution, one row per size per sample
+pub const SIZES_HEADER: &str = "iteration,components,count";
+
+/// FNV-1a, stable across platforms and compiler versions unlike the std hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn to_json(comp: &ComponentDef) -> String {
+    serde_json::to_string(comp).expect("component definitions always serialise")
+}
+
+/// <def> with its components sorted by their serialised definition, ties broken by what each is
+/// bonded to, and its bonds remapped, each written lower end first, and sorted. Components alike
+/// in both are interchangeable, so their order does not change the name
+fn canonical(def: ParticleDef) -> ParticleDef {
+    let components: Vec<ComponentDef> = def
+        .components
+        .into_iter()
+        .map(|comp| match comp {
+            ComponentDef::Particle(inner) => ComponentDef::Particle(canonical(inner)),
+            atom => atom,
+        })
+        .collect();
+    let names: Vec<String> = components.iter().map(to_json).collect();
+    // (own site, neighbour, neighbour's site) of every bond of each component
+    let mut ends: Vec<Vec<(usize, &str, usize)>> = vec![vec![]; components.len()];
+    for b in &def.bonds {
+        ends[b.comp_a].push((b.site_a, &names[b.comp_b], b.site_b));
+        ends[b.comp_b].push((b.site_b, &names[b.comp_a], b.site_a));
+    }
+    for e in ends.iter_mut() {
+        e.sort();
+    }
+
+    let mut order: Vec<usize> = (0..components.len()).collect();
+    order.sort_by(|x, y| (&names[*x], &ends[*x]).cmp(&(&names[*y], &ends[*y])));
+    let mut position = vec![0; order.len()];
+    for (new, old) in order.iter().enumerate() {
+        position[*old] = new;
+    }
+
+    let mut bonds: Vec<Bond> = def
+        .bonds
+        .iter()
+        .map(|b| {
+            let a = (position[b.comp_a], b.site_a);
+            let z = (position[b.comp_b], b.site_b);
+            let (a, z) = if a <= z { (a, z) } else { (z, a) };
+            Bond {
+                comp_a: a.0,
+                site_a: a.1,
+                comp_b: z.0,
+                site_b: z.1,
+            }
+        })
+        .collect();
+    bonds.sort_by_key(|b| (b.comp_a, b.site_a, b.comp_b, b.site_b));
+
+    let mut components: Vec<Option<ComponentDef>> = components.into_iter().map(Some).collect();
+    ParticleDef {
+        components: order
+            .iter()
+            .map(|old| components[*old].take().unwrap())
+            .collect(),
+        bonds,
+    }
+}
+
+/// Name of the species of <particle>
+pub fn species_name(particle: &Particle) -> String {
+    let def = serde_json::to_string(&canonical(particle.to_particle_def()))
+        .expect("particle definitions always serialise");
+    format!("{:016x}", fnv1a(def.as_bytes()))
+}
+
+/// The population at one iteration
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub iteration: u64,
+    pub particles: usize,
+    pub atoms: usize,
+    pub free_sites: usize,
+    /// Bonds formed and broken since the previous sample
+    pub bonds_formed: u64,
+    pub bonds_broken: u64,
+    /// Components and number of particles of each species
+    pub species: BTreeMap<String, (usize, usize)>,
+    /// Number of particles with each number of components
+    pub sizes: BTreeMap<usize, usize>,
+}
+
+impl Sample {
+    /// Shannon index of the species, -sum p ln p over the fraction p of particles of each
+    pub fn shannon(&self) -> f64 {
+        if self.particles == 0 {
+            return 0.0;
+        }
+        let total = self.particles as f64;
+        // subtracted from 0 so a single species gives 0 rather than -0
+        0.0 - self
+            .species
+            .values()
+            .map(|(_components, count)| {
+                let p = *count as f64 / total;
+                p * p.ln()
+            })
+            .sum::<f64>()
+    }
+
+    pub fn stats_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{:.6}",
+            self.iteration,
+            self.particles,
+            self.atoms,
+            self.free_sites,
+            self.bonds_formed,
+            self.bonds_broken,
+            self.species.len(),
+            self.shannon()
+        )
+    }
+
+    pub fn species_rows(&self) -> Vec<String> {
+        self.species
+            .iter()
+            .map(|(name, (components, count))| {
+                format!("{},{},{},{}", self.iteration, name, components, count)
+            })
+            .collect()
+    }
+
+    pub fn size_rows(&self) -> Vec<String> {
+        self.sizes
+            .iter()
+            .map(|(size, count)| format!("{},{},{}", self.iteration, size, count))
+            .collect()
+    }
+}
+
+/// Takes samples, remembering the bond counts of the last one so each sample has the bonds of its
+/// own interval
+pub struct Sampler {
+    bonds_formed: u64,
+    bonds_broken: u64,
+}
+
+impl Sampler {
+    /// Sampler for a run that has formed and broken <bonds_formed> and <bonds_broken> bonds so
+    /// far, zero unless it is resumed
+    pub fn new(bonds_formed: u64, bonds_broken: u64) -> Sampler {
+        Sampler {
+            bonds_formed,
+            bonds_broken,
+        }
+    }
+
+    /// Samples <particles> at <iteration>, given the run's bond totals
+    pub fn sample(
+        &mut self,
+        iteration: u64,
+        particles: &[Particle],
+        bonds_formed: u64,
+        bonds_broken: u64,
+    ) -> Sample {
+        let mut species = BTreeMap::new();
+        for particle in particles {
+            let size = particle.components.len();
+            species.entry(species_name(particle)).or_insert((size, 0)).1 += 1;
+        }
+        let sample = Sample {
+            iteration,
+            particles: particles.len(),
+            atoms: particles.iter().map(|p| p.components.len()).sum(),
+            free_sites: particles.iter().map(|p| p.get_free_sites().len()).sum(),
+            bonds_formed: bonds_formed - self.bonds_formed,
+            bonds_broken: bonds_broken - self.bonds_broken,
+            species,
+            sizes: size_distribution(particles),
+        };
+        self.bonds_formed = bonds_formed;
+        self.bonds_broken = bonds_broken;
+        sample
+    }
+}
+
+/// Writes samples to up to three CSV tables, any of which may be left out
+pub struct StatsWriter<W: Write> {
+    stats: Option<W>,
+    species: Option<W>,
+    sizes: Option<W>,
+}
+
+impl<W: Write> StatsWriter<W> {
+    /// Writes the header of every table given
+    pub fn new(
+        mut stats: Option<W>,
+        mut species: Option<W>,
+        mut sizes: Option<W>,
+    ) -> io::Result<StatsWriter<W>> {
+        for (out, header) in &mut [
+            (&mut stats, STATS_HEADER),
+            (&mut species, SPECIES_HEADER),
+            (&mut sizes, SIZES_HEADER),
+        ] {
+            if let Some(out) = out.as_mut() {
+                writeln!(out, "{}", header)?;
+            }
+        }
+        Ok(StatsWriter {
+            stats,
+            species,
+            sizes,
+        })
+    }
+
+    pub fn write(&mut self, sample: &Sample) -> io::Result<()> {
+        if let Some(out) = self.stats.as_mut() {
+            writeln!(out, "{}", sample.stats_row())?;
+        }
+        if let Some(out) = self.species.as_mut() {
+            for row in sample.species_rows() {
+                writeln!(out, "{}", row)?;
+            }
+        }
+        if let Some(out) = self.sizes.as_mut() {
+            for row in sample.size_rows() {
+                writeln!(out, "{}", row)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes every table, returning the writers
+    pub fn into_inner(self) -> io::Result<(Option<W>, Option<W>, Option<W>)> {
+        let mut outs = (self.stats, self.species, self.sizes);
+        for out in [&mut outs.0, &mut outs.1, &mut outs.2].iter_mut() {
+            if let Some(out) = out.as_mut() {
+                out.flush()?;
+            }
+        }
+        Ok(outs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbn::RBN;
+    use crate::reactor::{make_atom, Reactor, ReactorConfig};
+    use crate::util::grouping::SitePolicy;
+    use crate::util::rng::seeded;
+
+    #[test]
+    fn names_ignore_bond_order() {
+        let atom = |seed| make_atom(RBN::new_with_rng(2, 10, 0.5, &mut seeded(seed)), 0);
+        let copy = |p: &Particle| p.to_particle_def().to_particle(0).unwrap();
+        let (a, b) = (atom(1), atom(2));
+        assert_ne!(species_name(&a), species_name(&b));
+
+        let ab = copy(&a).bond_with((0, 0), copy(&b), (0, 1));
+        let ba = copy(&b).bond_with((0, 1), copy(&a), (0, 0));
+        assert_ne!(ab.to_particle_def(), ba.to_particle_def());
+        assert_eq!(species_name(&ab), species_name(&ba));
+        let other = copy(&a).bond_with((0, 0), copy(&b), (0, 0));
+        assert_ne!(species_name(&ab), species_name(&other));
+
+        // the same atom twice, bonded by different sites
+        let aa = copy(&a).bond_with((0, 0), copy(&a), (0, 1));
+        let aa_swapped = copy(&a).bond_with((0, 1), copy(&a), (0, 0));
+        assert_eq!(species_name(&aa), species_name(&aa_swapped));
+    }
+
+    #[test]
+    fn samples_of_a_run() {
+        let config = ReactorConfig {
+            species: 2,
+            copies: 5,
+            n: 10,
+            k: 2,
+            bias: 0.5,
+            temperature: 0,
+            iterations: 100,
+            seed: 3,
+            break_probability: 0.2,
+            sites: SitePolicy::default(),
+            flow: None,
+            metropolis: false,
+        };
+        let mut reactor = Reactor::new(config);
+        let mut sampler = Sampler::new(0, 0);
+        let first = sampler.sample(0, &reactor.particles, 0, 0);
+        // two species of five atoms each
+        assert_eq!(first.species.len(), 2);
+        assert!((first.shannon() - 2f64.ln()).abs() < 1e-9);
+        assert_eq!(first.sizes.get(&1), Some(&10));
+
+        let mut samples = vec![first];
+        reactor
+            .run_checkpointed(25, |r| {
+                samples.push(sampler.sample(
+                    r.iteration,
+                    &r.particles,
+                    r.bonds_formed,
+                    r.bonds_broken,
+                ));
+                Ok(())
+            })
+            .unwrap();
+        let formed: u64 = samples.iter().map(|s| s.bonds_formed).sum();
+        assert_eq!(formed, reactor.bonds_formed);
+        assert!(samples.iter().all(|s| s.atoms == 10));
+
+        let mut out = StatsWriter::new(Some(Vec::new()), Some(Vec::new()), None).unwrap();
+        out.write(&samples[0]).unwrap();
+        let (stats, species, sizes) = out.into_inner().unwrap();
+        let stats = String::from_utf8(stats.unwrap()).unwrap();
+        assert!(stats.starts_with(STATS_HEADER));
+        assert!(stats.ends_with(",0,0,2,0.693147\n"));
+        assert_eq!(
+            String::from_utf8(species.unwrap()).unwrap().lines().count(),
+            3
+        );
+        assert!(sizes.is_none());
+    }
+}
@@ -0,0 +1,149 @@
+//! Exhaustive state transition graph of an RBN. Only feasible for small atoms since there are
+//! 2^n states
+use crate::rbn::{RBNState, RBN};
+use crate::util::cycle_calc::IsSynchronous;
+
+/// Largest RBN (in Nodes) we are willing to enumerate the state space of
+pub const MAX_STATE_SPACE_NODES: usize = 20;
+
+#[derive(Debug)]
+pub struct StateSpace {
+    /// Number of Nodes, states are the integers 0..2^n with Node i as bit i
+    pub n: usize,
+    /// successor[s] is the state s steps to
+    pub successor: Vec<u64>,
+    /// Index into attractors of the attractor each state ends up on
+    pub attractor: Vec<usize>,
+    /// Steps from each state until it is on its attractor, 0 for cycle states
+    pub depth: Vec<u64>,
+    /// Cycle states of each attractor in the order they are visited
+    pub attractors: Vec<Vec<u64>>,
+    /// Number of states (including the cycle) draining into each attractor
+    pub basin_size: Vec<usize>,
+}
+
+impl StateSpace {
+    /// Steps every state of <rbn> once and finds the attractors and basins of the resulting graph.
+    /// This overwrites the current state of the RBN
+    pub fn from_rbn(rbn: &RBN) -> StateSpace {
+        let n = rbn.get_node_count();
+        if n > MAX_STATE_SPACE_NODES {
+            panic!(
+                "State space of {} Nodes is too large, at most {} are supported",
+                n, MAX_STATE_SPACE_NODES
+            );
+        }
+        let mut successor = Vec::with_capacity(1 << n);
+        for state in 0..(1u64 << n) {
+            rbn.set_state(&RBNState::from(state));
+            let next = rbn.step();
+            rbn.sync();
+            successor.push(u64::from(&next));
+        }
+        StateSpace::from_successors(n, successor)
+    }
+
+    fn from_successors(n: usize, successor: Vec<u64>) -> StateSpace {
+        const UNSEEN: usize = usize::MAX;
+        let size = successor.len();
+        let mut attractor = vec![UNSEEN; size];
+        let mut depth = vec![0u64; size];
+        let mut attractors = Vec::<Vec<u64>>::new();
+        // walk number that first visited a state, so we can tell our own path from older ones
+        let mut walk_id = vec![UNSEEN; size];
+        for start in 0..size {
+            if attractor[start] != UNSEEN {
+                continue;
+            }
+            let mut path = Vec::new();
+            let mut cur = start;
+            while attractor[cur] == UNSEEN && walk_id[cur] == UNSEEN {
+                walk_id[cur] = start;
+                path.push(cur);
+                cur = successor[cur] as usize;
+            }
+            let mut tail_len = path.len();
+            if attractor[cur] == UNSEEN {
+                // we ran into our own path, everything from cur onwards is a new cycle
+                let cycle_start = path.iter().position(|s| *s == cur).unwrap();
+                let id = attractors.len();
+                attractors.push(path[cycle_start..].iter().map(|s| *s as u64).collect());
+                for s in &path[cycle_start..] {
+                    attractor[*s] = id;
+                    depth[*s] = 0;
+                }
+                tail_len = cycle_start;
+            }
+            // label the transient part of the path back to front
+            for s in path[..tail_len].iter().rev() {
+                let next = successor[*s] as usize;
+                attractor[*s] = attractor[next];
+                depth[*s] = depth[next] + 1;
+            }
+        }
+        let mut basin_size = vec![0; attractors.len()];
+        for a in &attractor {
+            basin_size[*a] += 1;
+        }
+        StateSpace {
+            n,
+            successor,
+            attractor,
+            depth,
+            attractors,
+            basin_size,
+        }
+    }
+
+    /// States visited starting from <init> up to and including the first cycle state
+    pub fn path_to_attractor(&self, init: u64) -> Vec<u64> {
+        let mut path = vec![init];
+        let mut cur = init;
+        while self.depth[cur as usize] != 0 {
+            cur = self.successor[cur as usize];
+            path.push(cur);
+        }
+        path
+    }
+
+    /// Whether <state> lies on an attractor cycle
+    pub fn is_cycle_state(&self, state: u64) -> bool {
+        self.depth[state as usize] == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_space_attractors() {
+        // 0 -> 1 -> 2 -> 1, 3 -> 3
+        let space = StateSpace::from_successors(2, vec![1, 2, 1, 3]);
+        assert_eq!(space.attractors, vec![vec![1, 2], vec![3]]);
+        assert_eq!(space.attractor, vec![0, 0, 0, 1]);
+        assert_eq!(space.depth, vec![1, 0, 0, 0]);
+        assert_eq!(space.basin_size, vec![3, 1]);
+        assert_eq!(space.path_to_attractor(0), vec![0, 1]);
+    }
+
+    #[test]
+    fn state_space_rbn() {
+        let tbls = vec![
+            vec![true, false, false, true],
+            vec![false, true, true, true],
+            vec![true, true, false, false],
+        ];
+        let rbn = RBN::new_from_def(tbls, vec![(1, 2), (0, 2), (2, 2)]);
+        let space = StateSpace::from_rbn(&rbn);
+        assert_eq!(space.basin_size.iter().sum::<usize>(), 8);
+        for cycle in &space.attractors {
+            // stepping through a cycle returns to where it started
+            let mut cur = cycle[0];
+            for expected in cycle.iter().skip(1).chain(cycle.iter().take(1)) {
+                cur = space.successor[cur as usize];
+                assert_eq!(cur, *expected);
+            }
+        }
+    }
+}
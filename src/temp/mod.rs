@@ -1,3 +1,13 @@
 pub type Temperature = u16; // This needs to be wrapped correct to be a 12 bit vector but BitVec isn't in stable yet
                             // More importantly this currently means you can't set more then 16
                             // nodes because of the sizing
+
+use bit_field::BitField;
+
+/// State of <n> Nodes started from <temperature>, Node i takes bit i and Nodes past bit 15 are
+/// off. Every calculation from a Temperature starts from this state
+pub fn bits(temperature: Temperature, n: usize) -> Vec<bool> {
+    (0..n)
+        .map(|idx| idx < 16 && temperature.get_bit(idx))
+        .collect()
+}
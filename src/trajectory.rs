@@ -0,0 +1,125 @@
+//! Recorded RBN trajectories, the transient followed by one pass of the cycle
+use crate::rbn::{RBNState, RBN};
+use crate::temp::Temperature;
+use crate::util::bonding::IsSubSymbolic;
+use crate::util::cycle_calc::IsSynchronous;
+use crate::util::recorder::{Phase, Recorder};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trajectory {
+    /// Node states at each time step, states[t][i] is Node i at step t. The first <transient> rows
+    /// are the transient, the following <cycle> rows are one pass of the attractor
+    pub states: Vec<Vec<bool>>,
+    pub transient: usize,
+    pub cycle: usize,
+}
+
+impl Trajectory {
+    /// Calculates the particle from <init_state> and records every state from <init_state> to the
+    /// end of the first pass through the cycle
+    pub fn record(rbn: &mut RBN, init_state: Temperature) -> Trajectory {
+        let mut traj = Trajectory {
+            states: vec![],
+            transient: 0,
+            cycle: 0,
+        };
+        rbn.calculate_particle(init_state, &mut traj);
+        traj
+    }
+
+    /// Runs <rbn> from <init>, Node i taking <init[i]>, until a state repeats. Every state is
+    /// kept so this finds the attractor exactly for any number of Nodes
+    pub fn from_state(rbn: &RBN, init: &[bool]) -> Trajectory {
+        rbn.set_state(&RBNState::from(init.to_vec()));
+        let mut seen = HashMap::<Vec<bool>, usize>::new();
+        let mut states = vec![rbn.get_current_states()];
+        seen.insert(states[0].clone(), 0);
+        loop {
+            rbn.step();
+            rbn.sync();
+            let state = rbn.get_current_states();
+            if let Some(first) = seen.get(&state) {
+                return Trajectory {
+                    transient: *first,
+                    cycle: states.len() - first,
+                    states,
+                };
+            }
+            seen.insert(state.clone(), states.len());
+            states.push(state);
+        }
+    }
+
+    /// States of one pass through the attractor
+    pub fn cycle_states(&self) -> &[Vec<bool>] {
+        &self.states[self.transient..]
+    }
+
+    /// Number of Nodes in each recorded state
+    pub fn node_count(&self) -> usize {
+        self.states.first().map_or(0, |s| s.len())
+    }
+
+    /// Liveliness of each Node over the cycle rows, +1 for every step on and -1 for every step off
+    pub fn cycle_liveliness(&self) -> Vec<i32> {
+        let mut live = vec![0; self.node_count()];
+        for row in &self.states[self.transient..] {
+            for (idx, val) in row.iter().enumerate() {
+                live[idx] += if *val { 1 } else { -1 };
+            }
+        }
+        live
+    }
+}
+
+/// Keeps the transient and cycle states, the cycle search is not part of the trajectory
+impl Recorder for Trajectory {
+    fn record_state(&mut self, phase: Phase, _step: u64, state: &[bool]) {
+        match phase {
+            Phase::Transient => self.transient += 1,
+            Phase::Cycle => self.cycle += 1,
+            Phase::CycleSearch => return,
+        }
+        self.states.push(state.to_vec());
+    }
+    fn record_liveliness(&mut self, _phase: Phase, _liveliness: &[i32]) {}
+    fn record_event(&mut self, _name: &str, _value: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_cycle() {
+        // n0 = !n1, n1 = n0 cycles through 4 states, n2 = 0 gives a transient of 1 from n2 = 1
+        let tbls = vec![vec![true, false], vec![false, true], vec![false, false]];
+        let mut rbn = RBN::new_from_def_k(tbls, vec![vec![1], vec![0], vec![2]]);
+        let traj = Trajectory::record(&mut rbn, 0b100);
+        assert_eq!(traj.transient, 1);
+        assert_eq!(traj.cycle, 4);
+        assert_eq!(
+            traj.states,
+            vec![
+                vec![false, false, true],
+                vec![true, false, false],
+                vec![true, true, false],
+                vec![false, true, false],
+                vec![false, false, false],
+            ]
+        );
+        assert_eq!(traj.cycle_liveliness(), vec![0, 0, -4]);
+        let exact = Trajectory::from_state(&rbn, &[false, false, true]);
+        assert_eq!(exact, traj);
+
+        // starting on the cycle there is no transient, the first row is the start
+        let traj = Trajectory::record(&mut rbn, 0b001);
+        assert_eq!(traj.transient, 0);
+        assert_eq!(traj.cycle, 4);
+        assert_eq!(traj.states[0], vec![true, false, false]);
+        assert_eq!(traj, Trajectory::from_state(&rbn, &[true, false, false]));
+        assert_eq!(rbn.get_liveliness(Phase::Transient), vec![0, 0, 0]);
+        assert_eq!(traj.cycle_liveliness(), rbn.get_liveliness(Phase::Cycle));
+    }
+}
@@ -1,29 +1,74 @@
-use crate::node::Node;
+use crate::node::NodeRef;
 use crate::temp::Temperature;
-use std::cell::RefCell;
+use crate::util::grouping::SitePolicy;
+use crate::util::recorder::Recorder;
 use std::fmt;
-use std::rc::Rc;
 ///Particles are either stable or unstable depending on the sub-symbolic dynamics. Unstable
-///particles undergo some stabalising action (such as decomposition)
-/// TODO This needs to be redone correctly, I think an RBN_Properties stcuture is not a bad idea to
-/// store the relevant bits of data that aren't stability specific (eg cycle and transient)
-#[derive(Debug)]
+///particles undergo some stabalising action (such as decomposition). Either way the cycle and
+///transient are those of the whole particle's dynamics
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Stability {
-    Stable,
+    /// Every bond still joins sites whose spikes can bond, always true of an atom
+    Stable { cycle: u64, transient: u64 },
+    /// At least one bond no longer joins sites whose spikes can bond
     Unstable { cycle: u64, transient: u64 },
 }
 
-#[derive(Debug)]
+impl Stability {
+    pub fn is_stable(&self) -> bool {
+        match self {
+            Stability::Stable { .. } => true,
+            Stability::Unstable { .. } => false,
+        }
+    }
+
+    /// (cycle, transient) of the dynamics
+    pub fn lengths(&self) -> (u64, u64) {
+        match *self {
+            Stability::Stable { cycle, transient } | Stability::Unstable { cycle, transient } => {
+                (cycle, transient)
+            }
+        }
+    }
+}
+
+/// True if sites with spikes <a> and <b> can bond, their signs have to be opposite
+pub fn spikes_bond(a: i32, b: i32) -> bool {
+    (a < 0 && b > 0) || (a > 0 && b < 0)
+}
+
+/// Energy of a bond between sites with spikes <a> and <b>, minus their difference, so the further
+/// apart the spikes the more stable the bond
+pub fn bond_energy(a: i32, b: i32) -> i64 {
+    -(i64::from(a) - i64::from(b)).abs()
+}
+
+#[derive(Debug, Clone)]
 pub struct BondingSite {
-    interaction_list: Vec<Rc<RefCell<Node>>>,
+    interaction_list: Vec<NodeRef>,
 }
 
 impl BondingSite {
-    pub fn new(il: Vec<Rc<RefCell<Node>>>) -> BondingSite {
+    pub fn new(il: Vec<NodeRef>) -> BondingSite {
         BondingSite {
             interaction_list: il,
         }
     }
+
+    /// Nodes that make up the interaction group of this site
+    pub fn get_interaction_list(&self) -> &Vec<NodeRef> {
+        &self.interaction_list
+    }
+}
+
+/// A bond between two components of a Particle. Components are indexes into
+/// Particle::components, sites are indexes into that component's get_all_bonding_sites()
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bond {
+    pub comp_a: usize,
+    pub site_a: usize,
+    pub comp_b: usize,
+    pub site_b: usize,
 }
 
 impl fmt::Display for BondingSite {
@@ -43,7 +88,10 @@ impl fmt::Display for BondingSite {
 /// A Particle which IsSubSymbolic must be recaluclated when system changes in order to determine
 /// if the particle's internal state has changed  
 pub trait IsSubSymbolic {
-    fn calculate_particle(&mut self, init_state: Temperature, verbose: bool) -> Stability;
+    /// Runs the sub-symbolic dynamics from <init_state>, reporting what it visits to <rec>. The
+    /// dynamics must be deterministic: a probabilistic RBN has no single cycle to find, so
+    /// calculating one, or a particle with one among its atoms, panics. See pbn for those
+    fn calculate_particle(&mut self, init_state: Temperature, rec: &mut dyn Recorder) -> Stability;
 }
 
 /// A particle which is bondable has a number of bonding sites each of which some associated
@@ -52,6 +100,9 @@ pub trait IsBondable {
     /// Generates the bonding sites based on the underlying representatin
     fn generate_bonding_sites(&mut self) -> Vec<BondingSite>;
 
+    /// Generates the bonding sites following <policy> instead of the particle's own
+    fn generate_bonding_sites_with(&mut self, policy: &SitePolicy) -> Vec<BondingSite>;
+
     /// Returns Bonding Property for a specific &BondingSite
     /// If the BondingSite is not present on the particle returns None
     fn get_bonding_prop(&self, bs: &BondingSite) -> Option<i32>;
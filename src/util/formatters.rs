@@ -10,4 +10,7 @@ pub trait IsFormatable {
     fn fmt_cycle_liveliness(&self) -> String;
     /// Returns transient liveliness TODO rename to generic version
     fn fmt_trans_liveliness(&self) -> String;
+    /// Returns the body of a Graphviz DOT graph of the component. All graph node and cluster
+    /// names start with <prefix> so several components can be drawn in the same graph
+    fn fmt_dot(&self, prefix: &str) -> String;
 }
@@ -0,0 +1,594 @@
+//! Strategies for splitting an RBN into interaction groups, the Nodes behind each bonding site.
+//! Apart from fixed partitions every strategy grows a group from a seed Node by repeatedly moving
+//! to the preferred neighbour that is not in a group yet, and starts a new group when it is full
+//! or stuck
+use crate::config::ConfigError;
+use crate::rbn::RBN;
+use crate::util::bonding::BondingSite;
+use crate::util::recorder::Phase;
+use crate::util::rng::seeded;
+use rand::Rng;
+use std::collections::BTreeSet;
+
+/// Builds the interaction groups of an RBN
+pub trait GroupStrategy {
+    /// Interaction groups of <rbn>, none with more than <max_group_size> Nodes
+    fn interaction_groups(&self, rbn: &RBN, max_group_size: u16) -> Vec<BondingSite>;
+}
+
+/// Nodes fed by Node <idx>, each once and in index order
+fn targets(rbn: &RBN, idx: usize) -> Vec<usize> {
+    (0..rbn.get_node_count())
+        .filter(|t| *t != idx && rbn.get_node_inputs(*t).contains(&idx))
+        .collect()
+}
+
+/// Greedy growth shared by the strategies. <pick_seed> chooses the first Node of a group from the
+/// unassigned ones, <next> chooses where to go from a Node given the unassigned ones
+fn grow<S, N>(rbn: &RBN, max_group_size: u16, mut pick_seed: S, mut next: N) -> Vec<BondingSite>
+where
+    S: FnMut(&BTreeSet<usize>) -> usize,
+    N: FnMut(usize, &BTreeSet<usize>) -> Option<usize>,
+{
+    let mut free: BTreeSet<usize> = (0..rbn.get_node_count()).collect();
+    let mut groups = vec![];
+    while !free.is_empty() {
+        let mut current = pick_seed(&free);
+        free.remove(&current);
+        let mut group = vec![current];
+        while group.len() < max_group_size as usize {
+            match next(current, &free) {
+                Some(idx) => {
+                    free.remove(&idx);
+                    group.push(idx);
+                    current = idx;
+                }
+                None => break,
+            }
+        }
+        groups.push(BondingSite::new(
+            group.iter().map(|idx| rbn.get_node(*idx)).collect(),
+        ));
+    }
+    groups
+}
+
+/// The original ordering, see RBN::generate_interaction_groups_inf
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Influence {
+    pub least_first: bool,
+}
+
+impl GroupStrategy for Influence {
+    fn interaction_groups(&self, rbn: &RBN, max_group_size: u16) -> Vec<BondingSite> {
+        rbn.generate_interaction_groups_inf(max_group_size, self.least_first)
+    }
+}
+
+/// Seeds groups with the Node feeding the most (or fewest) other Nodes and follows the Nodes it
+/// feeds rather than its inputs, the best connected target first. Ties go to the lowest index
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutDegree {
+    pub least_first: bool,
+}
+
+impl GroupStrategy for OutDegree {
+    fn interaction_groups(&self, rbn: &RBN, max_group_size: u16) -> Vec<BondingSite> {
+        let out: Vec<Vec<usize>> = (0..rbn.get_node_count())
+            .map(|idx| targets(rbn, idx))
+            .collect();
+        let least = self.least_first;
+        let best = |candidates: &mut dyn Iterator<Item = usize>| {
+            candidates.fold(None, |best: Option<usize>, idx| match best {
+                Some(b)
+                    if (least && out[b].len() <= out[idx].len())
+                        || (!least && out[b].len() >= out[idx].len()) =>
+                {
+                    Some(b)
+                }
+                _ => Some(idx),
+            })
+        };
+        grow(
+            rbn,
+            max_group_size,
+            |free| best(&mut free.iter().cloned()).unwrap(),
+            |idx, free| best(&mut out[idx].iter().cloned().filter(|t| free.contains(t))),
+        )
+    }
+}
+
+/// Random walks over the wiring, inputs and targets alike, each group starting from a random
+/// unassigned Node. The walk is drawn from <seed> so the groups are reproducible
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RandomWalk {
+    pub seed: u64,
+}
+
+impl GroupStrategy for RandomWalk {
+    fn interaction_groups(&self, rbn: &RBN, max_group_size: u16) -> Vec<BondingSite> {
+        let neighbours: Vec<BTreeSet<usize>> = (0..rbn.get_node_count())
+            .map(|idx| {
+                let mut all: BTreeSet<usize> = targets(rbn, idx).into_iter().collect();
+                all.extend(rbn.get_node_inputs(idx).iter().filter(|i| **i != idx));
+                all
+            })
+            .collect();
+        // both closures draw from the one generator
+        let rng = std::cell::RefCell::new(seeded(self.seed));
+        grow(
+            rbn,
+            max_group_size,
+            |free| {
+                let pick = rng.borrow_mut().gen_range(0, free.len());
+                *free.iter().nth(pick).unwrap()
+            },
+            |idx, free| {
+                let open: Vec<&usize> = neighbours[idx].intersection(free).collect();
+                if open.is_empty() {
+                    None
+                } else {
+                    Some(*open[rng.borrow_mut().gen_range(0, open.len())])
+                }
+            },
+        )
+    }
+}
+
+/// Seeds groups with the Node whose cycle liveliness is furthest from (or closest to) zero and
+/// follows its inputs in the same order, so lively Nodes end up together. Ties go to the lowest
+/// index. An RBN that has not been calculated has no liveliness and is grouped by Influence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LivelinessWeighted {
+    pub least_first: bool,
+}
+
+impl GroupStrategy for LivelinessWeighted {
+    fn interaction_groups(&self, rbn: &RBN, max_group_size: u16) -> Vec<BondingSite> {
+        if !rbn.is_calculated() {
+            let least_first = self.least_first;
+            return Influence { least_first }.interaction_groups(rbn, max_group_size);
+        }
+        let live: Vec<i32> = rbn
+            .get_liveliness(Phase::Cycle)
+            .iter()
+            .map(|l| l.abs())
+            .collect();
+        let least = self.least_first;
+        let best = |candidates: &mut dyn Iterator<Item = usize>| {
+            candidates.fold(None, |best: Option<usize>, idx| match best {
+                Some(b) if (least && live[b] <= live[idx]) || (!least && live[b] >= live[idx]) => {
+                    Some(b)
+                }
+                _ => Some(idx),
+            })
+        };
+        grow(
+            rbn,
+            max_group_size,
+            |free| best(&mut free.iter().cloned()).unwrap(),
+            |idx, free| {
+                best(
+                    &mut rbn
+                        .get_node_inputs(idx)
+                        .iter()
+                        .cloned()
+                        .filter(|i| free.contains(i)),
+                )
+            },
+        )
+    }
+}
+
+/// Groups given up front as lists of Node indexes. Groups longer than the largest group size are
+/// cut into pieces of that size, Nodes in no group are left out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partitions {
+    pub groups: Vec<Vec<usize>>,
+}
+
+impl Partitions {
+    /// Checks every index is a Node of an RBN of <n> Nodes and no Node is in two groups
+    pub fn check(&self, n: usize) -> Result<(), ConfigError> {
+        let mut seen = BTreeSet::new();
+        for idx in self.groups.iter().flatten() {
+            if *idx >= n {
+                return Err(ConfigError::Parse(format!(
+                    "Node {} is not in an atom of {} Nodes",
+                    idx, n
+                )));
+            }
+            if !seen.insert(*idx) {
+                return Err(ConfigError::Parse(format!(
+                    "Node {} is in more than one group",
+                    idx
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GroupStrategy for Partitions {
+    fn interaction_groups(&self, rbn: &RBN, max_group_size: u16) -> Vec<BondingSite> {
+        self.groups
+            .iter()
+            .filter(|group| !group.is_empty())
+            .flat_map(|group| group.chunks((max_group_size as usize).max(1)))
+            .map(|chunk| BondingSite::new(chunk.iter().map(|idx| rbn.get_node(*idx)).collect()))
+            .collect()
+    }
+}
+
+/// The built in strategies as configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Grouping {
+    Influence {
+        #[serde(default)]
+        least_first: bool,
+    },
+    OutDegree {
+        #[serde(default)]
+        least_first: bool,
+    },
+    RandomWalk {
+        #[serde(default)]
+        seed: u64,
+    },
+    Liveliness {
+        #[serde(default)]
+        least_first: bool,
+    },
+    Partitions {
+        groups: Vec<Vec<usize>>,
+    },
+}
+
+impl Default for Grouping {
+    fn default() -> Grouping {
+        Grouping::Influence { least_first: false }
+    }
+}
+
+impl GroupStrategy for Grouping {
+    fn interaction_groups(&self, rbn: &RBN, max_group_size: u16) -> Vec<BondingSite> {
+        match self {
+            Grouping::Influence { least_first } => Influence {
+                least_first: *least_first,
+            }
+            .interaction_groups(rbn, max_group_size),
+            Grouping::OutDegree { least_first } => OutDegree {
+                least_first: *least_first,
+            }
+            .interaction_groups(rbn, max_group_size),
+            Grouping::RandomWalk { seed } => {
+                RandomWalk { seed: *seed }.interaction_groups(rbn, max_group_size)
+            }
+            Grouping::Liveliness { least_first } => LivelinessWeighted {
+                least_first: *least_first,
+            }
+            .interaction_groups(rbn, max_group_size),
+            Grouping::Partitions { groups } => Partitions {
+                groups: groups.clone(),
+            }
+            .interaction_groups(rbn, max_group_size),
+        }
+    }
+}
+
+/// What happens to interaction groups smaller than the minimum size
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmallGroups {
+    /// The group is dropped and its Nodes are in no bonding site
+    Discard,
+    /// The group joins a group it is wired to, or the one before it if there is none
+    Merge,
+}
+
+fn default_min_group_size() -> u16 {
+    1
+}
+
+fn default_small() -> SmallGroups {
+    SmallGroups::Merge
+}
+
+/// How an atom's Nodes become bonding sites. The default is one site per interaction group
+/// grown by influence, up to the whole atom, with every Node in exactly one site
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SitePolicy {
+    #[serde(default)]
+    pub grouping: Grouping,
+    /// Largest interaction group, the atom's Node count if not given
+    #[serde(default)]
+    pub max_group_size: Option<u16>,
+    /// Groups smaller than this are discarded or merged, see <small>
+    #[serde(default = "default_min_group_size")]
+    pub min_group_size: u16,
+    #[serde(default = "default_small")]
+    pub small: SmallGroups,
+    /// Groups that stop short of the largest size carry on through the inputs of their last Node
+    /// even when those are already in another group, so a Node may be in several sites
+    #[serde(default)]
+    pub overlap: bool,
+    /// Number of bonding sites wanted, the smallest groups are merged while there are too many
+    /// and the largest split in two while there are too few. Splitting stops when it would go
+    /// below the minimum size
+    #[serde(default)]
+    pub sites: Option<usize>,
+}
+
+impl Default for SitePolicy {
+    fn default() -> SitePolicy {
+        SitePolicy {
+            grouping: Grouping::default(),
+            max_group_size: None,
+            min_group_size: default_min_group_size(),
+            small: default_small(),
+            overlap: false,
+            sites: None,
+        }
+    }
+}
+
+impl SitePolicy {
+    pub fn is_default(&self) -> bool {
+        *self == SitePolicy::default()
+    }
+
+    /// Checks the policy can be applied to an atom of <n> Nodes
+    pub fn check(&self, n: usize) -> Result<(), ConfigError> {
+        if let Grouping::Partitions { groups } = &self.grouping {
+            Partitions {
+                groups: groups.clone(),
+            }
+            .check(n)?;
+        }
+        if self.max_group_size == Some(0) || self.sites == Some(0) {
+            return Err(ConfigError::Parse(
+                "max_group_size and sites must be at least 1".to_string(),
+            ));
+        }
+        if let Some(max) = self.max_group_size {
+            if self.min_group_size > max {
+                return Err(ConfigError::Parse(format!(
+                    "min_group_size {} is larger than max_group_size {}",
+                    self.min_group_size, max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Bonding sites of <rbn> under this policy
+    pub fn bonding_sites(&self, rbn: &RBN) -> Vec<BondingSite> {
+        let n = rbn.get_node_count();
+        let max = self.max_group_size.unwrap_or(n as u16);
+        let mut groups: Vec<Vec<usize>> = self
+            .grouping
+            .interaction_groups(rbn, max)
+            .iter()
+            .map(|site| {
+                site.get_interaction_list()
+                    .iter()
+                    .map(|nd| nd.borrow().get_id() as usize)
+                    .collect()
+            })
+            .collect();
+
+        if self.overlap {
+            for group in groups.iter_mut() {
+                extend_overlapping(rbn, group, max as usize);
+            }
+        }
+
+        let min = self.min_group_size as usize;
+        match self.small {
+            SmallGroups::Discard => groups.retain(|g| g.len() >= min),
+            SmallGroups::Merge => {
+                while groups.len() > 1 {
+                    match groups.iter().position(|g| g.len() < min) {
+                        Some(idx) => merge_into_neighbour(rbn, &mut groups, idx),
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        if let Some(target) = self.sites {
+            while groups.len() > target {
+                let smallest = (0..groups.len())
+                    .min_by_key(|idx| groups[*idx].len())
+                    .unwrap();
+                merge_into_neighbour(rbn, &mut groups, smallest);
+            }
+            while groups.len() < target {
+                let largest = match (0..groups.len()).max_by_key(|idx| groups[*idx].len()) {
+                    Some(idx) => idx,
+                    None => break,
+                };
+                let len = groups[largest].len();
+                if len < 2 || len / 2 < min {
+                    break;
+                }
+                let tail = groups[largest].split_off(len - len / 2);
+                groups.insert(largest + 1, tail);
+            }
+        }
+
+        groups
+            .iter()
+            .map(|g| BondingSite::new(g.iter().map(|idx| rbn.get_node(*idx)).collect()))
+            .collect()
+    }
+}
+
+/// Grows <group> up to <max> Nodes through the most influential inputs of its last Node, taking
+/// Nodes already in other groups
+fn extend_overlapping(rbn: &RBN, group: &mut Vec<usize>, max: usize) {
+    while group.len() < max {
+        let last = *group.last().unwrap();
+        let next = rbn
+            .get_node(last)
+            .borrow()
+            .get_inputs_by_inf()
+            .iter()
+            .rev()
+            .map(|input| input.borrow().get_id() as usize)
+            .find(|idx| !group.contains(idx));
+        match next {
+            Some(idx) => group.push(idx),
+            None => break,
+        }
+    }
+}
+
+/// Removes group <idx> and adds its Nodes to the first other group wired to it, or to the group
+/// before it if none is
+fn merge_into_neighbour(rbn: &RBN, groups: &mut Vec<Vec<usize>>, idx: usize) {
+    let fragment = groups.remove(idx);
+    if groups.is_empty() {
+        groups.push(fragment);
+        return;
+    }
+    let wired = |other: &Vec<usize>| {
+        fragment.iter().any(|f| {
+            other
+                .iter()
+                .any(|o| rbn.get_node_inputs(*f).contains(o) || rbn.get_node_inputs(*o).contains(f))
+        })
+    };
+    let into = groups
+        .iter()
+        .position(wired)
+        .unwrap_or_else(|| idx.saturating_sub(1));
+    for nd in fragment {
+        if !groups[into].contains(&nd) {
+            groups[into].push(nd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::bonding::{IsBondable, IsSubSymbolic};
+    use crate::util::recorder::NullRecorder;
+
+    fn ids(sites: &[BondingSite]) -> String {
+        sites.iter().map(|s| format!("{}", s)).collect()
+    }
+
+    #[test]
+    fn strategies_on_a_known_network() {
+        // a chain 0 <- 1 <- 2 <- 3 with 3 reading itself, 0 also feeds 2 and 3
+        let tbls = vec![
+            vec![false, true, true, false],
+            vec![false, true, true, true],
+            vec![true, false, false, true],
+            vec![false, true, true, false],
+        ];
+        let mut rbn =
+            RBN::new_from_def_k(tbls, vec![vec![1, 1], vec![2, 2], vec![3, 0], vec![3, 0]]);
+        // with no liveliness to go by it groups as Influence does
+        assert!(!rbn.is_calculated());
+        assert_eq!(
+            ids(&LivelinessWeighted { least_first: true }.interaction_groups(&rbn, 2)),
+            ids(&rbn.generate_interaction_groups_inf(2, true))
+        );
+        rbn.calculate_particle(0b0101, &mut NullRecorder);
+        assert!(rbn.is_calculated());
+
+        let inf = Grouping::default().interaction_groups(&rbn, 4);
+        assert_eq!(
+            ids(&inf),
+            ids(&rbn.generate_interaction_groups_inf(4, false))
+        );
+
+        // 0 feeds two Nodes so starts, then its best connected target
+        let out = OutDegree { least_first: false }.interaction_groups(&rbn, 2);
+        assert_eq!(ids(&out), "[0, 2][1][3]");
+
+        let walk = RandomWalk { seed: 7 }.interaction_groups(&rbn, 4);
+        let mut covered: Vec<u16> = walk
+            .iter()
+            .flat_map(|s| s.get_interaction_list().iter().map(|n| n.borrow().get_id()))
+            .collect();
+        covered.sort();
+        assert_eq!(covered, vec![0, 1, 2, 3]);
+        assert_eq!(
+            ids(&walk),
+            ids(&RandomWalk { seed: 7 }.interaction_groups(&rbn, 4))
+        );
+
+        let live = LivelinessWeighted { least_first: false }.interaction_groups(&rbn, 1);
+        let cycle = rbn.get_liveliness(Phase::Cycle);
+        let first = live[0].get_interaction_list()[0].borrow().get_id() as usize;
+        assert!(cycle.iter().all(|l| l.abs() <= cycle[first].abs()));
+
+        let parts = Partitions {
+            groups: vec![vec![3, 0, 1], vec![]],
+        };
+        assert!(parts.check(4).is_ok());
+        assert!(parts.check(3).is_err());
+        assert_eq!(ids(&parts.interaction_groups(&rbn, 2)), "[3, 0][1]");
+    }
+
+    #[test]
+    fn site_policies() {
+        // n0 <- n1 <- n2 <- n3, n2 and n3 also read n0 and n3
+        let tbls = vec![vec![false, true, true, false]; 4];
+        let mut rbn =
+            RBN::new_from_def_k(tbls, vec![vec![1, 1], vec![2, 2], vec![3, 0], vec![3, 0]]);
+        let parts = |groups: Vec<Vec<usize>>| SitePolicy {
+            grouping: Grouping::Partitions { groups },
+            ..SitePolicy::default()
+        };
+
+        let merged = SitePolicy {
+            min_group_size: 2,
+            ..parts(vec![vec![0], vec![1, 2, 3]])
+        };
+        assert_eq!(ids(&merged.bonding_sites(&rbn)), "[1, 2, 3, 0]");
+        let discarded = SitePolicy {
+            small: SmallGroups::Discard,
+            ..merged.clone()
+        };
+        assert_eq!(ids(&discarded.bonding_sites(&rbn)), "[1, 2, 3]");
+
+        let split = SitePolicy {
+            sites: Some(3),
+            ..parts(vec![vec![0, 1, 2, 3]])
+        };
+        assert_eq!(ids(&split.bonding_sites(&rbn)), "[0, 1][2][3]");
+        let joined = SitePolicy {
+            sites: Some(1),
+            ..parts(vec![vec![0], vec![1], vec![2, 3]])
+        };
+        assert_eq!(ids(&joined.bonding_sites(&rbn)), "[2, 3, 1, 0]");
+
+        // both groups run on into the next Node, so Node 1 is in both sites
+        let overlapping = SitePolicy {
+            max_group_size: Some(2),
+            overlap: true,
+            ..parts(vec![vec![0], vec![1]])
+        };
+        assert_eq!(ids(&overlapping.bonding_sites(&rbn)), "[0, 1][1, 2]");
+
+        // the RBN keeps its policy and atom files carry it
+        rbn.set_site_policy(overlapping.clone());
+        assert_eq!(rbn.generate_bonding_sites().len(), 2);
+        let def = crate::formats::json::AtomDef::from_rbn(&rbn);
+        assert_eq!(def.to_rbn().unwrap().get_site_policy(), &overlapping);
+        assert!(SitePolicy {
+            min_group_size: 3,
+            max_group_size: Some(2),
+            ..SitePolicy::default()
+        }
+        .check(4)
+        .is_err());
+    }
+}
@@ -1,3 +1,6 @@
 pub mod bonding;
 pub mod cycle_calc;
 pub mod formatters;
+pub mod grouping;
+pub mod recorder;
+pub mod rng;
@@ -0,0 +1,215 @@
+use crate::util::bonding::BondingSite;
+use std::fmt;
+use std::io;
+use std::io::Write;
+
+/// Which part of the particle calculation a recorded state or liveliness belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// States visited while searching for the cycle length
+    CycleSearch,
+    /// States of the transient, starting from the initial state
+    Transient,
+    /// States of one pass through the attractor, following the transient
+    Cycle,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::CycleSearch => write!(f, "cycle_search"),
+            Phase::Transient => write!(f, "transient"),
+            Phase::Cycle => write!(f, "cycle"),
+        }
+    }
+}
+
+/// An observer handed to calculations that would otherwise have printed what they were doing.
+/// States are given with Node i at index i
+pub trait Recorder {
+    /// Called with every state visited, <step> counts from the initial state
+    fn record_state(&mut self, phase: Phase, step: u64, state: &[bool]);
+    /// Called with the liveliness of every Node once a phase is complete
+    fn record_liveliness(&mut self, phase: Phase, liveliness: &[i32]);
+    /// Called for anything else worth noting, such as cycle lengths or generated bonding sites
+    fn record_event(&mut self, name: &str, value: &str);
+}
+
+/// Recorder that throws everything away
+pub struct NullRecorder;
+
+impl Recorder for NullRecorder {
+    fn record_state(&mut self, _phase: Phase, _step: u64, _state: &[bool]) {}
+    fn record_liveliness(&mut self, _phase: Phase, _liveliness: &[i32]) {}
+    fn record_event(&mut self, _name: &str, _value: &str) {}
+}
+
+/// Records every bonding site in <sites> as a "bonding_site" event listing its Node ids
+pub fn record_bonding_sites(sites: &[BondingSite], rec: &mut dyn Recorder) {
+    for site in sites {
+        rec.record_event("bonding_site", &format!("{}", site));
+    }
+}
+
+fn bits(state: &[bool]) -> String {
+    state.iter().map(|s| if *s { '1' } else { '0' }).collect()
+}
+
+/// Writes one CSV row per record with the columns kind,phase,step,name,value. States are a bit
+/// string with Node 0 first, liveliness is ; separated with Node 0 first
+pub struct CsvRecorder<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> CsvRecorder<W> {
+    pub fn new(out: W) -> CsvRecorder<W> {
+        let mut rec = CsvRecorder { out, error: None };
+        rec.write_row("kind,phase,step,name,value");
+        rec
+    }
+
+    fn write_row(&mut self, row: &str) {
+        if self.error.is_none() {
+            if let Err(err) = writeln!(self.out, "{}", row) {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    /// Returns the writer, or the first error hit while writing
+    pub fn into_inner(self) -> io::Result<W> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.out),
+        }
+    }
+}
+
+/// Quotes a CSV field if it needs it
+fn csv_field(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl<W: Write> Recorder for CsvRecorder<W> {
+    fn record_state(&mut self, phase: Phase, step: u64, state: &[bool]) {
+        self.write_row(&format!("state,{},{},,{}", phase, step, bits(state)));
+    }
+    fn record_liveliness(&mut self, phase: Phase, liveliness: &[i32]) {
+        let values: Vec<String> = liveliness.iter().map(|l| l.to_string()).collect();
+        self.write_row(&format!("liveliness,{},,,{}", phase, values.join(";")));
+    }
+    fn record_event(&mut self, name: &str, value: &str) {
+        self.write_row(&format!("event,,,{},{}", csv_field(name), csv_field(value)));
+    }
+}
+
+/// Writes one JSON object per line per record
+pub struct JsonLinesRecorder<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> JsonLinesRecorder<W> {
+    pub fn new(out: W) -> JsonLinesRecorder<W> {
+        JsonLinesRecorder { out, error: None }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.error.is_none() {
+            if let Err(err) = writeln!(self.out, "{}", line) {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    /// Returns the writer, or the first error hit while writing
+    pub fn into_inner(self) -> io::Result<W> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.out),
+        }
+    }
+}
+
+fn json_string(val: &str) -> String {
+    let mut out = String::from("\"");
+    for c in val.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl<W: Write> Recorder for JsonLinesRecorder<W> {
+    fn record_state(&mut self, phase: Phase, step: u64, state: &[bool]) {
+        let values: Vec<&str> = state.iter().map(|s| if *s { "1" } else { "0" }).collect();
+        self.write_line(&format!(
+            "{{\"kind\":\"state\",\"phase\":\"{}\",\"step\":{},\"state\":[{}]}}",
+            phase,
+            step,
+            values.join(",")
+        ));
+    }
+    fn record_liveliness(&mut self, phase: Phase, liveliness: &[i32]) {
+        let values: Vec<String> = liveliness.iter().map(|l| l.to_string()).collect();
+        self.write_line(&format!(
+            "{{\"kind\":\"liveliness\",\"phase\":\"{}\",\"values\":[{}]}}",
+            phase,
+            values.join(",")
+        ));
+    }
+    fn record_event(&mut self, name: &str, value: &str) {
+        self.write_line(&format!(
+            "{{\"kind\":\"event\",\"name\":{},\"value\":{}}}",
+            json_string(name),
+            json_string(value)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_and_json_lines() {
+        let mut csv = CsvRecorder::new(Vec::new());
+        let mut json = JsonLinesRecorder::new(Vec::new());
+        for rec in [
+            &mut csv as &mut dyn Recorder,
+            &mut json as &mut dyn Recorder,
+        ]
+        .iter_mut()
+        {
+            rec.record_state(Phase::Cycle, 3, &[true, false, true]);
+            rec.record_liveliness(Phase::Transient, &[2, -1, 0]);
+            rec.record_event("bonding_site", "[4, 3]");
+        }
+        let csv = String::from_utf8(csv.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            csv,
+            "kind,phase,step,name,value\nstate,cycle,3,,101\nliveliness,transient,,,2;-1;0\nevent,,,bonding_site,\"[4, 3]\"\n"
+        );
+        let json = String::from_utf8(json.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(
+            lines[0],
+            "{\"kind\":\"state\",\"phase\":\"cycle\",\"step\":3,\"state\":[1,0,1]}"
+        );
+        assert_eq!(
+            lines[2],
+            "{\"kind\":\"event\",\"name\":\"bonding_site\",\"value\":\"[4, 3]\"}"
+        );
+    }
+}
@@ -0,0 +1,11 @@
+//! Seedable random number generation so runs can be reproduced
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+/// Generator used wherever a run has to be reproducible from its seed
+pub type SpikyRng = Pcg32;
+
+/// Generator for <seed>, the same seed always gives the same sequence
+pub fn seeded(seed: u64) -> SpikyRng {
+    SpikyRng::seed_from_u64(seed)
+}